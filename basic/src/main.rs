@@ -0,0 +1,73 @@
+//! Native entry point. Run `cargo native` in this directory.
+//!
+//! `cargo native -- --headless <PNG> [--size <WIDTH>x<HEIGHT>] [--time <MS>]` renders a single
+//! frame to a PNG file instead of opening a window.
+//!
+//! `cargo native -- --report` prints what the adapter supports, which is handy for bug reports.
+//!
+//! `cargo native -- --dev` reloads `src/example.wgsl` whenever it changes.
+
+#[cfg(not(target_arch = "wasm32"))]
+const USAGE: &str = "usage: cargo native -- [--headless <PNG> [--size <WIDTH>x<HEIGHT>] \
+    [--time <MS>]] [--report] [--dev]";
+
+/// Prints the message and the usage, then exits.
+#[cfg(not(target_arch = "wasm32"))]
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{message}\n{USAGE}");
+    std::process::exit(2);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut headless = None;
+    let (mut width, mut height) = (800, 600);
+    let mut time = 0.0;
+    let mut report = false;
+    let mut dev = false;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| exit_with_usage(&format!("{arg} requires a value")))
+        };
+        match arg.as_str() {
+            "--headless" => headless = Some(std::path::PathBuf::from(value())),
+            "--size" => {
+                let size = value();
+                let parsed = size
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+                (width, height) = parsed.unwrap_or_else(|| {
+                    exit_with_usage(&format!("--size must be <WIDTH>x<HEIGHT>, not {size}"))
+                });
+            }
+            "--time" => {
+                let ms = value();
+                time = ms.parse().unwrap_or_else(|_| {
+                    exit_with_usage(&format!("--time must be milliseconds, not {ms}"))
+                });
+            }
+            "--report" => report = true,
+            "--dev" => dev = true,
+            _ => exit_with_usage(&format!("unknown argument: {arg}")),
+        }
+    }
+
+    let result = if report {
+        basic::native::print_report()
+    } else if let Some(path) = headless {
+        basic::native::render_to_png(&path, width, height, time)
+    } else {
+        basic::native::run(dev);
+        Ok(())
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+// Browser version starts from `run()` in the library instead.
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,340 @@
+use crate::{SHADER, create_renderer, recover_renderer, watch_device};
+use renderer::{Context, Error, Input, Renderer, Target, wgpu};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use winit::{
+    application::ApplicationHandler,
+    dpi::PhysicalSize,
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoop},
+    keyboard::PhysicalKey,
+    window::{Window, WindowId},
+};
+
+/// Wheel pixels per line, as browsers scroll.
+const LINE_HEIGHT: f32 = 16.0;
+
+const TITLE: &str = "A basic example of wgpu";
+
+/// How often dev mode looks at the shader file.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Opens a window and runs the animation loop until the window is closed.
+///
+/// Backends are [`wgpu::Backends::PRIMARY`] by default. Set `WGPU_BACKEND` environment variable
+/// to choose others. e.g. `WGPU_BACKEND=gl`
+///
+/// * dev - Reloads `src/example.wgsl` whenever it changes. Errors go to the console and the
+///   window title, while the last good shader keeps drawing.
+pub fn run(dev: bool) {
+    let event_loop = EventLoop::new().unwrap();
+    let mut app = App { state: None, dev };
+    event_loop.run_app(&mut app).unwrap();
+}
+
+/// Renders a single frame without a window and writes it to a PNG file.
+///
+/// Adapter is chosen the same way as [`run`]. Set `WGPU_ADAPTER_NAME` environment variable to pick
+/// one by name. e.g. `WGPU_ADAPTER_NAME=llvmpipe` for a software adapter.
+///
+/// * width, height - Frame size in pixels.
+/// * time - Milliseconds as `requestAnimationFrame()` gives.
+pub fn render_to_png(path: &Path, width: u32, height: u32, time: f32) -> Result<(), Error> {
+    pollster::block_on(async {
+        // Creates a `wgpu::Instance`.
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY.with_env(),
+            ..Default::default()
+        });
+
+        // Creates a `wgpu::Adapter`, `wgpu::Device` and `wgpu::Queue`.
+        let context = Context::new(&instance, None, wgpu::Limits::default()).await?;
+        let info = context.adapter.get_info();
+        log!("Adapter: {} ({:?})", info.name, info.backend);
+
+        // Creates an offscreen texture, buffers and a render pipeline.
+        let mut renderer = create_renderer(context, None, width, height, 1.0)?;
+        renderer.try_render(time)?;
+
+        let Some(png) = renderer.read_png().await? else {
+            unreachable!("renders offscreen")
+        };
+        fs::write(path, png).map_err(|e| Error::Io(path.to_owned(), e))?;
+        log!("Wrote {}", path.display());
+        Ok(())
+    })
+}
+
+/// Prints what the adapter supports. Adapter is chosen the same way as [`run`].
+///
+/// No surface is reported, since it needs a window.
+pub fn print_report() -> Result<(), Error> {
+    pollster::block_on(async {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY.with_env(),
+            ..Default::default()
+        });
+        let context = Context::new(&instance, None, wgpu::Limits::default()).await?;
+        println!("{}", context.report(None));
+        Ok(())
+    })
+}
+
+struct App {
+    state: Option<State>,
+    dev: bool,
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.state.is_some() {
+            return;
+        }
+
+        let attributes = Window::default_attributes()
+            .with_title(TITLE)
+            .with_inner_size(PhysicalSize::new(800, 600));
+        let window = Arc::new(event_loop.create_window(attributes).unwrap());
+        match pollster::block_on(State::new(window, self.dev)) {
+            Ok(state) => {
+                state.window.request_redraw();
+                self.state = Some(state);
+            }
+            Err(e) => {
+                log!("{e}");
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => state.resize(size),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                state.renderer.uniform.data.scale = scale_factor as f32;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                state
+                    .input
+                    .pointer_move(position.x as f32, position.y as f32);
+            }
+            WindowEvent::MouseInput {
+                state: element_state,
+                button,
+                ..
+            } => state.mouse_input(element_state, button),
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (dx, dy) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (x * LINE_HEIGHT, y * LINE_HEIGHT),
+                    MouseScrollDelta::PixelDelta(position) => {
+                        (position.x as f32, position.y as f32)
+                    }
+                };
+                // Same direction as `WheelEvent`, positive when scrolling down.
+                state.input.scroll(-dx, -dy);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state: element_state,
+                        ..
+                    },
+                ..
+            } => {
+                // Debug format of `KeyCode` is the same as `KeyboardEvent.code` for most keys.
+                let code = format!("{code:?}");
+                match element_state {
+                    ElementState::Pressed => state.input.key_down(&code),
+                    ElementState::Released => state.input.key_up(&code),
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                state.render();
+                state.window.request_redraw();
+            }
+            _ => {}
+        }
+    }
+}
+
+struct State {
+    /// Kept to recreate the context after the device is lost.
+    instance: wgpu::Instance,
+    window: Arc<Window>,
+    renderer: Renderer,
+    /// WGSL source drawing currently, which recovery builds the pipeline from.
+    shader: String,
+    /// Shader file reloaded in dev mode.
+    watch: Option<ShaderWatch>,
+    input: Input,
+    /// When the animation loop started.
+    start: Instant,
+}
+
+impl State {
+    async fn new(window: Arc<Window>, dev: bool) -> Result<Self, Error> {
+        // Creates a `wgpu::Instance`.
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY.with_env(),
+            ..Default::default()
+        });
+
+        // Creates a `wgpu::Surface`.
+        let surface = instance.create_surface(Arc::clone(&window))?;
+
+        // Creates a `wgpu::Adapter`, `wgpu::Device` and `wgpu::Queue`.
+        let context = Context::new(&instance, Some(&surface), wgpu::Limits::default()).await?;
+        let info = context.adapter.get_info();
+        log!("Adapter: {} ({:?})", info.name, info.backend);
+        watch_device(&context);
+
+        // Creates buffers and a render pipeline.
+        let size = window.inner_size();
+        let scale = window.scale_factor() as f32;
+        let renderer = create_renderer(context, Some(surface), size.width, size.height, scale)?;
+
+        // The file is read on the first frame, as it may have changed since the build.
+        let watch = dev.then(|| {
+            ShaderWatch::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("src/example.wgsl"))
+        });
+
+        Ok(Self {
+            instance,
+            window,
+            renderer,
+            shader: SHADER.to_owned(),
+            watch,
+            input: Input::new(),
+            start: Instant::now(),
+        })
+    }
+
+    fn render(&mut self) {
+        // Tries again on the next frame if no device is available yet.
+        if self.renderer.context.is_lost() {
+            if let Err(e) = pollster::block_on(self.recover()) {
+                log!("Failed to recover: {e}");
+            }
+            return;
+        }
+
+        if let Some(source) = self.watch.as_mut().and_then(ShaderWatch::poll) {
+            self.reload_shader(source);
+        }
+
+        // Same unit as `requestAnimationFrame()` gives.
+        let time = self.start.elapsed().as_secs_f32() * 1000.0;
+        self.renderer.apply_input(&self.input);
+        self.renderer.render(time);
+        self.input.end_frame();
+    }
+
+    /// Recreates the context after the device is lost, and moves the renderer onto it.
+    async fn recover(&mut self) -> Result<(), Error> {
+        let Target::Surface(surface) = &self.renderer.target else {
+            unreachable!("draws onto the window");
+        };
+        let context = self
+            .renderer
+            .context
+            .recreate(&self.instance, Some(&surface.surface))
+            .await?;
+        recover_renderer(&mut self.renderer, context, &self.shader)
+    }
+
+    /// Replaces the pipeline if the source is valid. Otherwise, shows the error and keeps the
+    /// current one.
+    fn reload_shader(&mut self, source: String) {
+        let wgsl = wgpu::ShaderSource::Wgsl(source.as_str().into());
+        match pollster::block_on(self.renderer.create_pipeline(wgsl)) {
+            Ok(pipeline) => {
+                self.renderer.render_pipeline = pipeline;
+                self.shader = source;
+                self.window.set_title(TITLE);
+                log!("Reloaded the shader");
+            }
+            Err(e) => {
+                self.window.set_title(&format!("{TITLE} - shader error"));
+                log!("{e}");
+            }
+        }
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        // Minimized window can't be configured.
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        if self.renderer.resize(size.width, size.height) {
+            log!("Resized: ({}, {})", size.width, size.height);
+        }
+    }
+
+    fn mouse_input(&mut self, element_state: ElementState, button: MouseButton) {
+        // Bits as `MouseEvent.buttons`.
+        let buttons = match button {
+            MouseButton::Left => 1,
+            MouseButton::Right => 2,
+            MouseButton::Middle => 4,
+            _ => return,
+        };
+        match element_state {
+            ElementState::Pressed => {
+                self.input.button_down(buttons);
+                // Clicks on press, as it has always done.
+                if let (MouseButton::Left, Some([x, y])) = (button, self.input.pointer) {
+                    self.input.click(x, y);
+                }
+            }
+            ElementState::Released => self.input.button_up(buttons),
+        }
+    }
+}
+
+/// Shader file checked for changes by its modification time.
+struct ShaderWatch {
+    path: PathBuf,
+    /// `None` until the file is read.
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+impl ShaderWatch {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            modified: None,
+            checked: Instant::now() - WATCH_INTERVAL,
+        }
+    }
+
+    /// Returns the source if the file has changed since the last call.
+    /// Looks at the file at most once every [`WATCH_INTERVAL`].
+    fn poll(&mut self) -> Option<String> {
+        if self.checked.elapsed() < WATCH_INTERVAL {
+            return None;
+        }
+        self.checked = Instant::now();
+
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok()?;
+        if self.modified == Some(modified) {
+            return None;
+        }
+        // Editors may truncate the file before writing, so an empty one is read again later.
+        let source = fs::read_to_string(&self.path)
+            .ok()
+            .filter(|s| !s.is_empty())?;
+        self.modified = Some(modified);
+        Some(source)
+    }
+}
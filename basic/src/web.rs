@@ -0,0 +1,285 @@
+use crate::{SHADER, create_renderer, recover_renderer, watch_device};
+use renderer::{CapabilityReport, Context, Error, Input, Renderer, Target, wgpu};
+use std::{cell::RefCell, ops};
+use wasm_bindgen::prelude::*;
+
+thread_local! {
+    static STATE: RefCell<State> = panic!();
+}
+
+/// Starts drawing. Rejects if the canvas can't be drawn onto, e.g. no adapter is available.
+#[wasm_bindgen]
+pub async fn run() -> Result<(), JsError> {
+    // When panics, we can see error messages on the console.
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+
+    // Creates a new state.
+    let state = State::new()
+        .await
+        .map_err(|e| JsError::new(&e.to_string()))?;
+
+    // Registers event listerns.
+    add_event_listener("", "resize", || {
+        STATE.with_borrow_mut(|state| {
+            state.resize();
+        })
+    });
+    add_typed_event_listener("#canvas0", "mousemove", |event: web_sys::MouseEvent| {
+        STATE.with_borrow_mut(|state| {
+            let x = scaled(event.offset_x() as f64) as f32;
+            let y = scaled(event.offset_y() as f64) as f32;
+            state.input.pointer_move(x, y);
+        })
+    });
+    add_typed_event_listener("#canvas0", "click", |event: web_sys::MouseEvent| {
+        STATE.with_borrow_mut(|state| {
+            let x = scaled(event.offset_x() as f64) as f32;
+            let y = scaled(event.offset_y() as f64) as f32;
+            state.input.click(x, y);
+        })
+    });
+    for type_ in ["mousedown", "mouseup"] {
+        add_typed_event_listener("#canvas0", type_, |event: web_sys::MouseEvent| {
+            STATE.with_borrow_mut(|state| state.input.buttons = event.buttons())
+        });
+    }
+    add_typed_event_listener("#canvas0", "wheel", |event: web_sys::WheelEvent| {
+        STATE.with_borrow_mut(|state| {
+            state
+                .input
+                .scroll(event.delta_x() as f32, event.delta_y() as f32)
+        })
+    });
+    add_typed_event_listener("", "keydown", |event: web_sys::KeyboardEvent| {
+        STATE.with_borrow_mut(|state| state.input.key_down(&event.code()))
+    });
+    add_typed_event_listener("", "keyup", |event: web_sys::KeyboardEvent| {
+        STATE.with_borrow_mut(|state| state.input.key_up(&event.code()))
+    });
+
+    // Runs the animation loop.
+    state.request_animation_frame();
+
+    STATE.set(state);
+    Ok(())
+}
+
+/// Replaces the shader with the given WGSL source, for hot reloading in dev mode.
+/// Rejects with the compiler's message if it's invalid, and the last good shader keeps drawing.
+#[wasm_bindgen]
+pub async fn set_shader(source: String) -> Result<(), JsError> {
+    loop {
+        let (device, pipeline) = STATE.with_borrow(|state| {
+            let wgsl = wgpu::ShaderSource::Wgsl(source.as_str().into());
+            let device = state.renderer.context.device.clone();
+            (device, state.renderer.create_pipeline(wgsl))
+        });
+        let pipeline = pipeline.await.map_err(|e| JsError::new(&e.to_string()))?;
+        let applied = STATE.with_borrow_mut(|state| {
+            // Recovered from device loss meanwhile, so builds it again on the new device.
+            if state.renderer.context.device != device {
+                return false;
+            }
+            state.renderer.render_pipeline = pipeline;
+            state.shader.clone_from(&source);
+            true
+        });
+        if applied {
+            return Ok(());
+        }
+    }
+}
+
+/// Returns what the adapter and the canvas support as a JS object, for bug reports.
+/// Call this after `run()` is over.
+#[wasm_bindgen]
+pub fn capability_report() -> JsValue {
+    STATE.with_borrow(|state| serde_wasm_bindgen::to_value(&state.report).unwrap_throw())
+}
+
+struct State {
+    /// Kept to recreate the context after the device is lost.
+    instance: wgpu::Instance,
+    canvas: Canvas,
+    renderer: Renderer,
+    /// WGSL source drawing currently, which recovery builds the pipeline from.
+    shader: String,
+    input: Input,
+    report: CapabilityReport,
+    animate_callback: Closure<dyn FnMut(f32)>,
+}
+
+impl State {
+    async fn new() -> Result<Self, Error> {
+        // Creates a `wgpu::Instance`.
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::BROWSER_WEBGPU,
+            ..Default::default()
+        });
+
+        // Finds out the canvas.
+        let canvas = Canvas::new("#canvas0");
+
+        // Creates a `wgpu::Surface`.
+        let surface =
+            instance.create_surface(wgpu::SurfaceTarget::Canvas(canvas.element.clone()))?;
+
+        // Creates a `wgpu::Adapter`, `wgpu::Device` and `wgpu::Queue`.
+        let context = Context::new(&instance, Some(&surface), wgpu::Limits::default()).await?;
+        let report = context.report(Some(&surface));
+        watch_device(&context);
+
+        // Creates buffers and a render pipeline.
+        let scale = web_sys::window().unwrap().device_pixel_ratio() as f32;
+        let renderer = create_renderer(
+            context,
+            Some(surface),
+            canvas.width(),
+            canvas.height(),
+            scale,
+        )?;
+
+        // Creates an animation loop.
+        let animate_callback = Closure::<dyn FnMut(f32)>::new(|time: f32| {
+            STATE.with_borrow_mut(|state| {
+                if state.renderer.context.is_lost() {
+                    // Pauses the loop until a new device is ready.
+                    wasm_bindgen_futures::spawn_local(recover());
+                    return;
+                }
+                state.render(time);
+                state.request_animation_frame();
+            })
+        });
+
+        Ok(Self {
+            instance,
+            canvas,
+            renderer,
+            shader: SHADER.to_owned(),
+            input: Input::new(),
+            report,
+            animate_callback,
+        })
+    }
+
+    fn request_animation_frame(&self) {
+        web_sys::window()
+            .unwrap()
+            .request_animation_frame(self.animate_callback.as_ref().unchecked_ref())
+            .expect_throw("Failed to request an animation frame");
+    }
+
+    fn resize(&mut self) {
+        let new_width = scaled(self.canvas.client_width() as f64) as u32;
+        let new_height = scaled(self.canvas.client_height() as f64) as u32;
+        if new_width != self.canvas.width() || new_height != self.canvas.height() {
+            self.canvas.set_width(new_width);
+            self.canvas.set_height(new_height);
+            self.renderer.resize(new_width, new_height);
+
+            log!("Resized: ({new_width}, {new_height})");
+        }
+    }
+
+    fn render(&mut self, time: f32) {
+        self.renderer.apply_input(&self.input);
+        self.renderer.render(time);
+        self.input.end_frame();
+    }
+
+    fn recover(&mut self, context: Context) -> Result<(), Error> {
+        let Target::Surface(surface) = &self.renderer.target else {
+            unreachable!("draws onto the canvas");
+        };
+        self.report = context.report(Some(&surface.surface));
+        recover_renderer(&mut self.renderer, context, &self.shader)
+    }
+}
+
+/// Recreates the context after the device is lost, then resumes the animation loop.
+/// Tries again on the next frame if no device is available yet.
+async fn recover() {
+    let (instance, context) =
+        STATE.with_borrow(|state| (state.instance.clone(), state.renderer.context.clone()));
+    // WebGPU doesn't need the surface to find an adapter.
+    let result = match context.recreate(&instance, None).await {
+        Ok(context) => STATE.with_borrow_mut(|state| state.recover(context)),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        log!("Failed to recover: {e}");
+    }
+    STATE.with_borrow(|state| state.request_animation_frame());
+}
+
+/// HTML canvas
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    element: web_sys::HtmlCanvasElement,
+}
+
+impl Canvas {
+    pub fn new(selectors: &str) -> Self {
+        let window = web_sys::window().unwrap();
+        let document = window.document().unwrap();
+        let element = document.query_selector(selectors).unwrap().unwrap();
+        let canvas = element.dyn_into::<web_sys::HtmlCanvasElement>().unwrap();
+        let width = scaled(canvas.client_width() as f64) as u32;
+        let height = scaled(canvas.client_height() as f64) as u32;
+        canvas.set_width(width);
+        canvas.set_height(height);
+
+        Self { element: canvas }
+    }
+}
+
+impl ops::Deref for Canvas {
+    type Target = web_sys::HtmlCanvasElement;
+
+    fn deref(&self) -> &Self::Target {
+        &self.element
+    }
+}
+
+fn add_event_listener(selectors: &str, type_: &str, f: impl Fn() + 'static) {
+    let listener = Closure::<dyn Fn()>::new(f);
+    _add_event_listener(selectors, type_, listener.as_ref().unchecked_ref());
+    listener.forget(); // Leak, but it occurs just once
+}
+
+fn add_typed_event_listener<E: JsCast + 'static>(
+    selectors: &str,
+    type_: &str,
+    f: impl Fn(E) + 'static,
+) {
+    let listener =
+        Closure::<dyn Fn(_)>::new(move |event: web_sys::Event| f(event.unchecked_into()));
+    _add_event_listener(selectors, type_, listener.as_ref().unchecked_ref());
+    listener.forget(); // Leak, but it occurs just once
+}
+
+fn _add_event_listener(selectors: &str, type_: &str, listener: &web_sys::js_sys::Function) {
+    if selectors.is_empty() {
+        web_sys::window()
+            .unwrap()
+            .add_event_listener_with_callback(type_, listener)
+            .expect("Failed to add an event listener");
+    } else {
+        let element = web_sys::window()
+            .unwrap()
+            .document()
+            .unwrap()
+            .query_selector(selectors)
+            .unwrap()
+            .unwrap();
+        element
+            .add_event_listener_with_callback(type_, listener)
+            .expect("Failed to add an event listener");
+    }
+}
+
+fn scaled(value: f64) -> f64 {
+    let scale = web_sys::window().unwrap().device_pixel_ratio();
+    value * scale
+}
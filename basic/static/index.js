@@ -1,4 +1,38 @@
-import { run } from "../pkg/wasm-index.js";
+import { run, capability_report, set_shader } from "../pkg/wasm-index.js";
 
 // Run wasm
 await run();
+
+// What the adapter and the canvas support, for bug reports.
+console.log(capability_report());
+
+// Dev mode reloads the shader whenever it changes on the dev server.
+if (process.env.NODE_ENV === "development") {
+  watchShader("src/example.wgsl");
+}
+
+async function watchShader(url) {
+  const overlay = document.getElementById("shader_error");
+  let last = null;
+  for (;;) {
+    await new Promise((resolve) => setTimeout(resolve, 500));
+    const response = await fetch(url, { cache: "no-store" }).catch(() => null);
+    if (!response?.ok) {
+      // Not served, e.g. built by `npm run build`.
+      return;
+    }
+    const source = await response.text();
+    if (source === last) {
+      continue;
+    }
+    last = source;
+    try {
+      // The last good shader keeps drawing on errors.
+      await set_shader(source);
+      overlay.hidden = true;
+    } catch (e) {
+      overlay.textContent = e.message;
+      overlay.hidden = false;
+    }
+  }
+}
@@ -1,6 +1,15 @@
+const path = require("path");
 const { merge } = require("webpack-merge");
 const common = require("./webpack.common.js");
 
 module.exports = merge(common, {
-  mode: "development"
-});
\ No newline at end of file
+  mode: "development",
+  devServer: {
+    // Serves shader sources, which the page polls to reload them without reloading itself.
+    static: {
+      directory: path.resolve(__dirname, "src"),
+      publicPath: "/src",
+      watch: false,
+    },
+  },
+});
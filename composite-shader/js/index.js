@@ -1,4 +1,38 @@
-import { run } from "../pkg/wasm-index.js";
+import { run, set_shader_sources } from "../pkg/wasm-index.js";
 
 // Run wasm
 await run();
+
+// Dev mode reloads the shaders whenever they change on the dev server.
+if (process.env.NODE_ENV === "development") {
+  watchShaders("src/top.wgsl", "src/uniform.wgsl");
+}
+
+async function watchShaders(topUrl, uniformUrl) {
+  const overlay = document.getElementById("shader_error");
+  const fetchText = async (url) => {
+    const response = await fetch(url, { cache: "no-store" }).catch(() => null);
+    return response?.ok ? response.text() : null;
+  };
+  let last = null;
+  for (;;) {
+    await new Promise((resolve) => setTimeout(resolve, 500));
+    const [top, uniform] = await Promise.all([fetchText(topUrl), fetchText(uniformUrl)]);
+    if (top === null || uniform === null) {
+      // Not served, e.g. built by `npm run build`.
+      return;
+    }
+    if (top + "\0" + uniform === last) {
+      continue;
+    }
+    last = top + "\0" + uniform;
+    try {
+      // The last good shader keeps drawing on errors.
+      await set_shader_sources(top, uniform);
+      overlay.hidden = true;
+    } catch (e) {
+      overlay.textContent = e.message;
+      overlay.hidden = false;
+    }
+  }
+}
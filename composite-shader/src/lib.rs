@@ -1,11 +1,12 @@
-use std::mem;
-use wasm_bindgen::prelude::*;
-use wgpu::util::DeviceExt;
-use winit::{
-    dpi::PhysicalSize, event_loop::EventLoop, platform::web::WindowBuilderExtWebSys,
-    window::WindowBuilder,
+use renderer::{
+    wgpu, Context, DeviceEvent, Error, PermutationPipelines, Renderer, RendererDescriptor,
+    ShaderLibrary, QUAD_INDICES, QUAD_VERTICES,
 };
-use std::borrow::Cow;
+use shaders::{create_shader_library, top_defs, TOP, TOP_PERMUTATIONS};
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+
+mod shaders;
 
 macro_rules! log {
     ($($t:tt)*) => {
@@ -13,159 +14,81 @@ macro_rules! log {
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    pos: [f32; 3],
-    color: [f32; 3],
-}
-
-impl Vertex {
-    fn layout() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    // pos
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    // color
-                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-            ],
-        }
-    }
-}
-
-const VERTICES: &[Vertex] = &[
-    Vertex {
-        pos: [-1.0, 1.0, 0.0],  // Top-left
-        color: [1.0, 0.0, 1.0], // Magenta
-    },
-    Vertex {
-        pos: [-1.0, -1.0, 0.0], // Bottom-left
-        color: [0.0, 0.0, 1.0], // Blue
-    },
-    Vertex {
-        pos: [1.0, 1.0, 0.0],   // Top-right
-        color: [1.0, 1.0, 0.0], // Yellow
-    },
-    Vertex {
-        pos: [1.0, -1.0, 0.0],  // Bottom-right
-        color: [0.0, 1.0, 0.0], // Green
-    },
-];
-
-const INDICES: &[u32] = &[0, 1, 2, 2, 1, 3]; // CCW, quad
-
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Default)]
-struct UniformData {
-    mouse_move: [f32; 2],
-    mouse_click: [f32; 2],
-    resolution: [f32; 2],
-    time: f32,
-    _padding: f32,
-}
-
 #[derive(Debug)]
 struct State {
     window: web_sys::Window,
+    /// Kept to recreate the context after the device is lost.
+    instance: wgpu::Instance,
     canvas: web_sys::HtmlCanvasElement,
-    winit_window: winit::window::Window,
-    surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    surface_config: wgpu::SurfaceConfiguration,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    index_num: u32,
-    uniform_data: UniformData,
-    uniform_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
-    render_pipeline: wgpu::RenderPipeline,
+    renderer: Renderer,
+    /// Modules composed with naga_oil. Dev mode replaces them with reloaded ones.
+    library: ShaderLibrary,
+    /// Pipelines of all variants of `top.wgsl`, which one of is drawn with.
+    /// `None` while they're built again on the device recovery has made.
+    pipelines: Option<PermutationPipelines>,
+    /// Whether the variant drawn with has `UNIFORM` defined.
+    uniform: bool,
     animation_cb: Closure<dyn FnMut(f32)>,
 }
 
-static mut STATE: Option<State> = None;
+thread_local! {
+    static STATE: RefCell<State> = panic!();
+}
 
 impl State {
-    async fn new() -> Self {
+    async fn new() -> Result<Self, Error> {
         // window
         let window = get_window();
         // canvas
         let canvas = State::init_canvas(&window).expect_throw("Failed to get the canvas");
-        // winit window
-        let winit_window = State::create_window(&canvas).expect_throw("Failed to create a window");
         // wgpu instance
         let instance = State::create_instance();
         // wgpu surface
-        let surface = State::create_surface(&instance, &winit_window)
-            .expect_throw("Failed to create a surface");
-        // wgpu adapter
-        let adapter = State::create_adapter(&instance, &surface)
-            .await
-            .expect_throw("Failed to create an adapter");
-        // wgpu device and queue
-        let (device, queue) = State::create_device_and_queue(&adapter)
-            .await
-            .expect_throw("Failed to create a device and a queue");
-        // wgpu surface configuration
-        let surface_config = State::create_surface_configuration(
-            &surface,
-            &adapter,
-            &device,
-            canvas.width(),
-            canvas.height(),
-        );
-        // wgpu vertex buffer
-        let vertex_buffer = State::create_vertex_buffer(&device, bytemuck::cast_slice(VERTICES));
-        // wgpu index buffer
-        let index_buffer = State::create_index_buffer(&device, bytemuck::cast_slice(INDICES));
-        // wgpu uniform buffer
-        let uniform_data = UniformData {
-            resolution: [canvas.width() as f32, canvas.height() as f32],
-            mouse_move: [std::f32::MIN, std::f32::MIN],
-            mouse_click: [std::f32::MIN, std::f32::MIN],
-            ..Default::default()
-        };
-        let (uniform_buffer, uniform_layout, uniform_bind_group) =
-            State::create_uniform_buffer(&device, bytemuck::cast_slice(&[uniform_data][..]));
+        let surface = State::create_surface(&instance, &canvas)?;
+        // wgpu adapter, device and queue
+        let context = Context::new(
+            &instance,
+            Some(&surface),
+            wgpu::Limits::downlevel_webgl2_defaults(),
+        )
+        .await?;
+        State::watch_device(&context);
+        // naga_oil modules
+        let mut library =
+            create_shader_library(include_str!("top.wgsl"), include_str!("uniform.wgsl"))?;
         // wgpu shader module
-        let shader_module = State::create_shader_module(&device);
-        // wgpu render pipeline
-        let render_pipeline = State::create_render_pipeline(
-            &device,
-            &[&uniform_layout],
-            &shader_module,
-            &surface_config,
-        );
+        let shader_module = State::create_shader_module(&context.device, &mut library)?;
+        // wgpu surface configuration, buffers and render pipeline
+        let renderer = Renderer::new(
+            context,
+            surface,
+            &RendererDescriptor {
+                vertices: QUAD_VERTICES,
+                indices: QUAD_INDICES,
+                shader_module: &shader_module,
+                width: canvas.width(),
+                height: canvas.height(),
+                scale: 1.0, // Canvas size isn't scaled in this example.
+            },
+        )?;
+        // Pipelines of all variants, composed and validated ahead of drawing
+        let pipelines =
+            PermutationPipelines::new(&renderer, &mut library, &TOP_PERMUTATIONS)?.await?;
         // animation_loop
         let animation_cb = State::create_animation_loop();
 
-        Self {
+        let mut state = Self {
             window,
+            instance,
             canvas,
-            winit_window,
-            surface,
-            device,
-            queue,
-            surface_config,
-            vertex_buffer,
-            index_buffer,
-            index_num: INDICES.len() as u32,
-            uniform_data,
-            uniform_buffer,
-            uniform_bind_group,
-            render_pipeline,
+            renderer,
+            library,
+            pipelines: Some(pipelines),
+            uniform: true,
             animation_cb,
-        }
+        };
+        state.select_pipeline();
+        Ok(state)
     }
 
     fn init_canvas(window: &web_sys::Window) -> Option<web_sys::HtmlCanvasElement> {
@@ -178,28 +101,8 @@ impl State {
         Some(canvas)
     }
 
-    fn create_window(canvas: &web_sys::HtmlCanvasElement) -> Option<winit::window::Window> {
-        // winit's window writes its physical and logical sizes in the HTML element
-        // as attributes and inner style respectively
-        // So, we should put "!important" in CSS to overwrite the inner style
-        let window = WindowBuilder::new()
-            .with_inner_size(PhysicalSize::new(
-                canvas.client_width(),
-                canvas.client_height(),
-            ))
-            .with_canvas(Some(canvas.clone()))
-            .build(&EventLoop::new())
-            .ok()?;
-        log!(
-            "Window size: ({}, {})",
-            window.inner_size().width,
-            window.inner_size().height
-        );
-        Some(window)
-    }
-
     fn create_instance() -> wgpu::Instance {
-        wgpu::Instance::new(wgpu::InstanceDescriptor {
+        wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::BROWSER_WEBGPU,
             ..Default::default()
         })
@@ -207,141 +110,29 @@ impl State {
 
     fn create_surface(
         instance: &wgpu::Instance,
-        winit_window: &winit::window::Window,
-    ) -> Option<wgpu::Surface> {
-        unsafe { instance.create_surface(winit_window).ok() }
-    }
-
-    async fn create_adapter(
-        instance: &wgpu::Instance,
-        surface: &wgpu::Surface,
-    ) -> Option<wgpu::Adapter> {
-        instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                compatible_surface: Some(surface),
-                force_fallback_adapter: false,
-                ..Default::default()
-            })
-            .await
-    }
-
-    async fn create_device_and_queue(
-        adapter: &wgpu::Adapter,
-    ) -> Option<(wgpu::Device, wgpu::Queue)> {
-        adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::downlevel_webgl2_defaults(),
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .ok()
-    }
-
-    fn create_surface_configuration(
-        surface: &wgpu::Surface,
-        adapter: &wgpu::Adapter,
-        device: &wgpu::Device,
-        width: u32,
-        height: u32,
-    ) -> wgpu::SurfaceConfiguration {
-        let surface_caps = surface.get_capabilities(adapter);
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_caps.formats[0],
-            width,
-            height,
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-        };
-        surface.configure(device, &surface_config);
-        surface_config
-    }
-
-    fn create_vertex_buffer(device: &wgpu::Device, contents: &[u8]) -> wgpu::Buffer {
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex buffer"),
-            contents,
-            usage: wgpu::BufferUsages::VERTEX,
-        })
-    }
-
-    fn create_index_buffer(device: &wgpu::Device, contents: &[u8]) -> wgpu::Buffer {
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index buffer"),
-            contents,
-            usage: wgpu::BufferUsages::INDEX,
-        })
+        canvas: &web_sys::HtmlCanvasElement,
+    ) -> Result<wgpu::Surface<'static>, wgpu::CreateSurfaceError> {
+        instance.create_surface(wgpu::SurfaceTarget::Canvas(canvas.clone()))
     }
 
-    fn create_uniform_buffer(
+    fn create_shader_module(
         device: &wgpu::Device,
-        contents: &[u8],
-    ) -> (wgpu::Buffer, wgpu::BindGroupLayout, wgpu::BindGroup) {
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Uniform buffer"),
-            contents,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Uniform bind group layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Uniform bind group"),
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-        });
-        (buffer, bind_group_layout, bind_group)
-    }
-
-    fn create_shader_module(device: &wgpu::Device) -> wgpu::ShaderModule {
+        library: &mut ShaderLibrary,
+    ) -> Result<wgpu::ShaderModule, Error> {
         // Creates shader module from a single file.
-        let by_single_file = || -> wgpu::ShaderModule {
-            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        let by_single_file = || -> Result<wgpu::ShaderModule, Error> {
+            Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("Shader module"),
                 source: wgpu::ShaderSource::Wgsl(include_str!("monolithic.wgsl").into()),
-            })
+            }))
         };
 
         // Composes shader module using naga_oil composition.
-        let by_naga_oil = || -> wgpu::ShaderModule {
-            use naga_oil::compose::{Composer, ComposableModuleDescriptor, NagaModuleDescriptor};
-            let mut composer = Composer::default();
-            composer.add_composable_module(ComposableModuleDescriptor {
-                source: include_str!("uniform.wgsl"), // Path to the file relative to the current file
-                file_path: "uniform.wgsl", // Path to the file relative to the Cargo.toml
-                ..Default::default()
-            }).unwrap();
-            let naga_module = composer.make_naga_module(NagaModuleDescriptor {
-                source: include_str!("top.wgsl"), // Path to the file relative to the current file
-                file_path: "example.wgsl", // Path to the file relative to the Cargo.toml
-                shader_defs: [("UNIFORM".to_owned(), Default::default())].into(),
-                ..Default::default()
-            }).unwrap();
-            device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("Shader module"),
-                source: wgpu::ShaderSource::Naga(Cow::Owned(naga_module)),
-            })
+        let by_naga_oil = || -> Result<wgpu::ShaderModule, Error> {
+            library.create_shader_module(device, TOP, &top_defs(true))
         };
 
-        let by_my_wgsl = || -> wgpu::ShaderModule {
+        let by_my_wgsl = || -> Result<wgpu::ShaderModule, Error> {
             use my_wgsl::*;
 
             #[wgsl_decl_struct]
@@ -349,6 +140,7 @@ impl State {
                 mouse_move: vec2<f32>,
                 mouse_click: vec2<f32>,
                 resolution: vec2<f32>,
+                scale: f32,
                 time: f32,
             }
 
@@ -391,14 +183,18 @@ impl State {
 
             let wgsl = builder.build();
 
-            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("Shader module"),
                 source: wgpu::ShaderSource::Wgsl(wgsl.into()),
-            })
+            }))
         };
 
         // With measurement.
-        fn with_measure (title: &str, id: &str, f: impl FnOnce() -> wgpu::ShaderModule) -> wgpu::ShaderModule {
+        fn with_measure(
+            title: &str,
+            id: &str,
+            f: impl FnOnce() -> Result<wgpu::ShaderModule, Error>,
+        ) -> Result<wgpu::ShaderModule, Error> {
             let s = now();
             let res = f();
             let e = now();
@@ -408,64 +204,63 @@ impl State {
         }
 
         // Let's try all of them.
-        with_measure("Creating shader module from a single file", "measure_single_file", by_single_file);
-        with_measure("Compositing shader module using naga_oil", "measure_naga_oil", by_naga_oil);
+        with_measure("Creating shader module from a single file", "measure_single_file", by_single_file)?;
+        with_measure("Compositing shader module using naga_oil", "measure_naga_oil", by_naga_oil)?;
         with_measure("Compositing shader module using my_wgsl", "measure_my_wgsl", by_my_wgsl)
     }
 
-    fn create_render_pipeline(
-        device: &wgpu::Device,
-        bind_group_layouts: &[&wgpu::BindGroupLayout],
-        shader_module: &wgpu::ShaderModule,
-        surface_config: &wgpu::SurfaceConfiguration,
-    ) -> wgpu::RenderPipeline {
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render pipeline layout"),
-                bind_group_layouts,
-                push_constant_ranges: &[],
-            });
-        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: shader_module,
-                entry_point: "v_main",
-                buffers: &[Vertex::layout()],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: shader_module,
-                entry_point: "f_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-        })
+    /// Logs device loss and uncaptured errors. The animation loop recovers lost devices.
+    fn watch_device(context: &Context) {
+        context.watch(|event| match event {
+            DeviceEvent::Lost { reason, message } => {
+                log!("Device lost ({:?}): {}", reason, message);
+            }
+            DeviceEvent::Error(error) => {
+                log!("Uncaptured error: {}", error);
+            }
+        });
+    }
+
+    /// Moves the renderer onto the new context, e.g. after the device is lost. The shader module is
+    /// created again on the new device from the library's cached modules, and so are the pipelines
+    /// of all variants, which the returned future gives. Pipelines of the lost device are dropped
+    /// meanwhile, so the renderer keeps its own pipeline for the current variant.
+    fn recover(
+        &mut self,
+        context: Context,
+    ) -> Result<impl std::future::Future<Output = Result<PermutationPipelines, Error>>, Error> {
+        self.pipelines = None;
+        let shader_module =
+            self.library
+                .create_shader_module(&context.device, TOP, &top_defs(self.uniform))?;
+        self.renderer.recover(context, &shader_module)?;
+        PermutationPipelines::new(&self.renderer, &mut self.library, &TOP_PERMUTATIONS)
+    }
+
+    /// Draws with the pipeline of the current variant.
+    fn select_pipeline(&mut self) {
+        // Selected once they're built after recovery.
+        let Some(pipelines) = &self.pipelines else {
+            return;
+        };
+        let defs = top_defs(self.uniform);
+        let pipeline = pipelines
+            .select(&defs)
+            .expect_throw("Unlisted variant");
+        self.renderer.render_pipeline = pipeline.clone();
     }
 
     fn create_animation_loop() -> Closure<dyn FnMut(f32)> {
-        Closure::<dyn FnMut(f32)>::new(|time: f32| unsafe {
-            let state = STATE.as_mut().unwrap_unchecked();
-            state.render(time);
-            state.request_animation_frame();
+        Closure::<dyn FnMut(f32)>::new(|time: f32| {
+            STATE.with_borrow_mut(|state| {
+                if state.renderer.context.is_lost() {
+                    // Pauses the loop until a new device is ready.
+                    wasm_bindgen_futures::spawn_local(recover());
+                    return;
+                }
+                state.renderer.render(time);
+                state.request_animation_frame();
+            })
         })
     }
 
@@ -483,14 +278,7 @@ impl State {
             // Synchronize manually
             self.canvas.set_width(new_width);
             self.canvas.set_height(new_height);
-            self.winit_window
-                .set_inner_size(PhysicalSize::new(new_width, new_height));
-            self.surface_config.width = new_width;
-            self.surface_config.height = new_height;
-            self.surface.configure(&self.device, &self.surface_config);
-
-            // Update uniform data
-            self.uniform_data.resolution = [new_width as f32, new_height as f32];
+            self.renderer.resize(new_width, new_height);
 
             log!("Resized: ({}, {})", new_width, new_height);
         }
@@ -498,59 +286,14 @@ impl State {
 
     fn mousemove(&mut self, event: web_sys::MouseEvent) {
         // Update uniform data
-        self.uniform_data.mouse_move = [event.offset_x() as f32, event.offset_y() as f32];
+        self.renderer
+            .mouse_move(event.offset_x() as f32, event.offset_y() as f32);
     }
 
     fn click(&mut self, event: web_sys::MouseEvent) {
         // Update uniform data
-        self.uniform_data.mouse_click = [event.offset_x() as f32, event.offset_y() as f32];
-    }
-
-    fn render(&mut self, time: f32) {
-        // Write uniform data to its buffer
-        self.uniform_data.time = time * 0.001;
-        self.queue.write_buffer(
-            &self.uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[self.uniform_data][..]),
-        );
-
-        let surface_texture = self.surface.get_current_texture().unwrap();
-        let texture_view = surface_texture.texture.create_view(&Default::default());
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render command encoder"),
-            });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &texture_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.draw_indexed(0..self.index_num, 0, 0..1);
-        }
-        self.queue.submit(std::iter::once(encoder.finish()));
-        surface_texture.present();
+        self.renderer
+            .mouse_click(event.offset_x() as f32, event.offset_y() as f32);
     }
 }
 
@@ -560,24 +303,110 @@ pub fn main() {
     console_log::init_with_level(log::Level::Warn).expect("Couldn't initialize logger");
 }
 
+/// Starts drawing. Rejects if the canvas can't be drawn onto, e.g. no adapter is available.
 #[wasm_bindgen]
-pub async fn run() {
-    unsafe {
-        STATE = Some(State::new().await);
-        let state = STATE.as_mut().unwrap_unchecked();
-
-        // Sets resize event listener on window.
-        add_event_listener("", "resize", || STATE.as_mut().unwrap_unchecked().resize());
+pub async fn run() -> Result<(), JsError> {
+    let state = State::new()
+        .await
+        .map_err(|e| JsError::new(&e.to_string()))?;
+
+    // Sets resize event listener on window.
+    add_event_listener("", "resize", || {
+        STATE.with_borrow_mut(|state| state.resize())
+    });
+
+    // Sets mousemove/click event listener on canvas
+    add_event_listener_with_mouseevent("canvas0", "mousemove", |event: web_sys::MouseEvent| {
+        STATE.with_borrow_mut(|state| state.mousemove(event))
+    });
+    add_event_listener_with_mouseevent("canvas0", "click", |event: web_sys::MouseEvent| {
+        STATE.with_borrow_mut(|state| state.click(event))
+    });
+    state.request_animation_frame();
+
+    STATE.set(state);
+    Ok(())
+}
 
-        // Sets mousemove/click event listener on canvas
-        add_event_listener_with_mouseevent("canvas0", "mousemove", |event: web_sys::MouseEvent| {
-            STATE.as_mut().unwrap_unchecked().mousemove(event)
+/// Composes the sources again and replaces the shader, for hot reloading in dev mode.
+/// Rejects with naga_oil's or wgpu's message if any variant is invalid, and the last good shader
+/// keeps drawing.
+#[wasm_bindgen]
+pub async fn set_shader_sources(top: String, uniform: String) -> Result<(), JsError> {
+    let to_js = |e: Error| JsError::new(&e.to_string());
+    let mut library = create_shader_library(&top, &uniform).map_err(to_js)?;
+    loop {
+        let (device, pipelines) = STATE.with_borrow(|state| {
+            let device = state.renderer.context.device.clone();
+            let pipelines =
+                PermutationPipelines::new(&state.renderer, &mut library, &TOP_PERMUTATIONS);
+            (device, pipelines)
         });
-        add_event_listener_with_mouseevent("canvas0", "click", |event: web_sys::MouseEvent| {
-            STATE.as_mut().unwrap_unchecked().click(event)
+        let pipelines = pipelines.map_err(to_js)?.await.map_err(to_js)?;
+
+        // Recovered from device loss meanwhile, so builds them again on the new device.
+        if STATE.with_borrow(|state| state.renderer.context.device != device) {
+            continue;
+        }
+        STATE.with_borrow_mut(|state| {
+            state.library = library;
+            state.pipelines = Some(pipelines);
+            state.select_pipeline();
         });
-        state.request_animation_frame();
+        return Ok(());
+    }
+}
+
+/// Switches between the variants of `top.wgsl` with and without `UNIFORM` defined.
+/// Both are built ahead of time, so the next frame is drawn with the other one.
+#[wasm_bindgen]
+pub fn set_uniform(enabled: bool) {
+    STATE.with_borrow_mut(|state| {
+        state.uniform = enabled;
+        state.select_pipeline();
+    })
+}
+
+/// Recovers from device loss, then resumes the animation loop.
+/// The loop tries again on the next frame if no device is available yet.
+async fn recover() {
+    if let Err(e) = try_recover().await {
+        log!("Failed to recover: {}", e);
+    }
+    STATE.with_borrow(|state| state.request_animation_frame());
+}
+
+/// Recreates the adapter and the device, then moves the state onto them.
+/// Nothing is borrowed while waiting, so events keep being handled meanwhile.
+async fn try_recover() -> Result<(), Error> {
+    let (instance, context) =
+        STATE.with_borrow(|state| (state.instance.clone(), state.renderer.context.clone()));
+    // WebGPU doesn't need the surface to find an adapter.
+    let context = context.recreate(&instance, None).await?;
+    State::watch_device(&context);
+    let device = context.device.clone();
+    let pipelines = STATE.with_borrow_mut(|state| state.recover(context))?;
+    let pipelines = match pipelines.await {
+        Ok(pipelines) => pipelines,
+        Err(e) => {
+            // Marks the new device lost as well, so the loop recovers again on the next frame.
+            STATE.with_borrow(|state| {
+                if state.renderer.context.device == device && state.pipelines.is_none() {
+                    state.renderer.context.set_lost();
+                }
+            });
+            return Err(e);
+        }
     };
+    STATE.with_borrow_mut(|state| {
+        // Hot reloading may have replaced them on the new device meanwhile.
+        if state.renderer.context.device == device && state.pipelines.is_none() {
+            state.pipelines = Some(pipelines);
+            state.select_pipeline();
+        }
+    });
+    log!("Recovered from device loss");
+    Ok(())
 }
 
 fn get_window() -> web_sys::Window {
@@ -0,0 +1,32 @@
+//! Shaders composed with naga_oil and the variants drawn with them.
+//!
+//! This file doesn't depend on the browser, so the golden tests validate every variant with it.
+
+use renderer::{Define, Error, Permutations, ShaderDefValue, ShaderDefs, ShaderLibrary};
+
+/// Shader importing `uniform.wgsl`.
+pub const TOP: &str = "top.wgsl";
+
+/// Variants of `top.wgsl`. Without `UNIFORM`, the mouse doesn't highlight anything.
+pub const TOP_PERMUTATIONS: Permutations = Permutations {
+    shader: TOP,
+    defines: &[Define::bool("UNIFORM")],
+    variants: &[&[], &[("UNIFORM", ShaderDefValue::Bool(true))]],
+};
+
+/// Registers `uniform.wgsl` and `top.wgsl` importing it.
+pub fn create_shader_library(top: &str, uniform: &str) -> Result<ShaderLibrary, Error> {
+    let mut library = ShaderLibrary::new();
+    library.add_module("uniform.wgsl", uniform)?; // Path to the file relative to the Cargo.toml
+    library.add_shader(TOP, top);
+    Ok(library)
+}
+
+/// Defs of the variant of `top.wgsl`.
+pub fn top_defs(uniform: bool) -> ShaderDefs {
+    if uniform {
+        [("UNIFORM".to_owned(), ShaderDefValue::Bool(true))].into()
+    } else {
+        ShaderDefs::new()
+    }
+}
@@ -4,5 +4,6 @@ struct UniformData {
     mouse_move: vec2<f32>,
     mouse_click: vec2<f32>,
     resolution: vec2<f32>,
+    scale: f32,
     time: f32,
 }
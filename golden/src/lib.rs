@@ -0,0 +1,261 @@
+//! Golden-image regression tests of what the examples draw.
+//!
+//! Each test in `tests/examples.rs` renders an example's shader and mesh into an offscreen texture
+//! with fixed uniform inputs, and compares the frame against `reference/<name>.png`. On mismatch,
+//! the rendered frame and a diff image are written to `target/golden/`.
+//! `tests/reload.rs` reloads shaders at runtime, checking that only valid ones replace the pipeline.
+//! `tests/library.rs` composes shaders sharing imports through a `ShaderLibrary`, and
+//! `tests/permutations.rs` validates every listed shader variant.
+//!
+//! Set `GOLDEN_UPDATE=1` environment variable to write rendered frames as new references instead.
+
+use renderer::{Context, Renderer, RendererDescriptor, Target, Vertex, wgpu};
+use std::{
+    env, fs,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+/// Frame width in pixels.
+pub const WIDTH: u32 = 200;
+/// Frame height in pixels.
+pub const HEIGHT: u32 = 150;
+/// Milliseconds as `requestAnimationFrame()` gives.
+pub const TIME: f32 = 1250.0;
+/// Mouse position in pixels.
+pub const MOUSE_MOVE: [f32; 2] = [60.0, 50.0];
+/// Mouse position in pixels.
+pub const MOUSE_CLICK: [f32; 2] = [140.0, 100.0];
+
+/// How far a frame may be from its reference image.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// Largest difference allowed in each channel of a pixel.
+    pub channel: u8,
+    /// Number of pixels allowed to exceed `channel`.
+    pub pixels: usize,
+}
+
+impl Default for Tolerance {
+    /// Software adapters and GPUs don't interpolate colors exactly the same, so channels may be
+    /// off by a few.
+    fn default() -> Self {
+        Self {
+            channel: 3,
+            pixels: 0,
+        }
+    }
+}
+
+/// An example to render.
+#[derive(Debug)]
+pub struct Case<'a> {
+    /// Name of the reference image without extension.
+    pub name: &'a str,
+    /// Shader having `v_main` and `f_main` entry points.
+    pub shader: wgpu::ShaderSource<'a>,
+    pub vertices: &'a [Vertex],
+    pub indices: &'a [u32],
+}
+
+/// Renders the case and compares the frame against its reference image.
+/// Panics if they don't match within the tolerance.
+pub fn check(case: Case<'_>, tolerance: Tolerance) {
+    let name = case.name.to_owned();
+    compare(&name, &render(case), tolerance);
+}
+
+/// Compares a frame against the reference image of the name.
+/// Panics if they don't match within the tolerance.
+pub fn compare(name: &str, actual: &Image, tolerance: Tolerance) {
+    let reference = reference_dir().join(format!("{name}.png"));
+
+    if env::var_os("GOLDEN_UPDATE").is_some() {
+        actual.save(&reference).unwrap();
+        return;
+    }
+
+    let expected = Image::load(&reference).unwrap_or_else(|e| {
+        panic!(
+            "failed to load {}: {e}. Run with GOLDEN_UPDATE=1 to create it.",
+            reference.display()
+        )
+    });
+    let Some(diff) = expected.diff(actual, tolerance) else {
+        return;
+    };
+
+    // Leaves what we've got for inspection.
+    let out_dir = out_dir();
+    fs::create_dir_all(&out_dir).unwrap();
+    let actual_path = out_dir.join(format!("{name}.actual.png"));
+    let diff_path = out_dir.join(format!("{name}.diff.png"));
+    actual.save(&actual_path).unwrap();
+    diff.image.save(&diff_path).unwrap();
+
+    panic!(
+        "{name}: {} pixels differ from {}. See {} and {}",
+        diff.mismatched,
+        reference.display(),
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+/// Renders the case offscreen with the fixed inputs.
+pub fn render(case: Case<'_>) -> Image {
+    capture(&mut create_renderer(case))
+}
+
+/// Creates a renderer drawing the case into an offscreen texture, with the fixed mouse inputs.
+///
+/// Backends are [`wgpu::Backends::PRIMARY`] and [`wgpu::Backends::GL`] by default. Set
+/// `WGPU_BACKEND` and `WGPU_ADAPTER_NAME` environment variables to choose others. e.g.
+/// `WGPU_BACKEND=gl WGPU_ADAPTER_NAME=llvmpipe`
+pub fn create_renderer(case: Case<'_>) -> Renderer {
+    pollster::block_on(async {
+        // Creates a `wgpu::Instance`.
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: (wgpu::Backends::PRIMARY | wgpu::Backends::GL).with_env(),
+            ..Default::default()
+        });
+
+        // Creates a `wgpu::Adapter`, `wgpu::Device` and `wgpu::Queue`.
+        let context = Context::new(&instance, None, wgpu::Limits::default())
+            .await
+            .unwrap();
+
+        // Creates a `wgpu::ShaderModule`.
+        let shader_module = context
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(case.name),
+                source: case.shader,
+            });
+
+        // Creates an offscreen texture, buffers and a render pipeline.
+        let mut renderer = Renderer::new_offscreen(
+            context,
+            &RendererDescriptor {
+                vertices: case.vertices,
+                indices: case.indices,
+                shader_module: &shader_module,
+                width: WIDTH,
+                height: HEIGHT,
+                scale: 1.0,
+            },
+        );
+        renderer.mouse_move(MOUSE_MOVE[0], MOUSE_MOVE[1]);
+        renderer.mouse_click(MOUSE_CLICK[0], MOUSE_CLICK[1]);
+        renderer
+    })
+}
+
+/// Renders a frame at the fixed time and reads it back.
+/// The renderer must be one of [`create_renderer`].
+pub fn capture(renderer: &mut Renderer) -> Image {
+    renderer.render(TIME);
+    pollster::block_on(async {
+        let Target::Offscreen(target) = &renderer.target else {
+            unreachable!()
+        };
+        Image {
+            width: target.width(),
+            height: target.height(),
+            pixels: target.read_pixels(&renderer.context).await.unwrap(),
+        }
+    })
+}
+
+/// RGBA8 image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Reads a PNG file. The file must be 8-bit RGBA as [`Image::save`] writes.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = BufReader::new(fs::File::open(path)?);
+        let mut reader = png::Decoder::new(file)
+            .read_info()
+            .map_err(io::Error::other)?;
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut pixels).map_err(io::Error::other)?;
+        if (info.color_type, info.bit_depth) != (png::ColorType::Rgba, png::BitDepth::Eight) {
+            return Err(io::Error::other(format!(
+                "expected 8-bit RGBA, but got {:?} {:?}",
+                info.bit_depth, info.color_type
+            )));
+        }
+        pixels.truncate(info.buffer_size());
+
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    /// Writes a PNG file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(
+            path,
+            renderer::encode_png(self.width, self.height, &self.pixels),
+        )
+    }
+
+    /// Compares `self` as the expected image with `actual`.
+    /// Returns `None` if they match within the tolerance.
+    pub fn diff(&self, actual: &Image, tolerance: Tolerance) -> Option<Diff> {
+        assert_eq!(
+            (self.width, self.height),
+            (actual.width, actual.height),
+            "image sizes differ"
+        );
+
+        // Mismatched pixels are red on top of the expected image dimmed.
+        let mut mismatched = 0;
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for (expected, actual) in self.pixels.chunks(4).zip(actual.pixels.chunks(4)) {
+            let over = expected
+                .iter()
+                .zip(actual)
+                .any(|(e, a)| e.abs_diff(*a) > tolerance.channel);
+            if over {
+                mismatched += 1;
+                pixels.extend_from_slice(&[255, 0, 0, 255]);
+            } else {
+                pixels.extend(expected[..3].iter().map(|c| c / 4));
+                pixels.push(255);
+            }
+        }
+
+        (mismatched > tolerance.pixels).then_some(Diff {
+            mismatched,
+            image: Image {
+                width: self.width,
+                height: self.height,
+                pixels,
+            },
+        })
+    }
+}
+
+/// Result of [`Image::diff`].
+#[derive(Debug)]
+pub struct Diff {
+    /// Number of pixels exceeding the channel tolerance.
+    pub mismatched: usize,
+    pub image: Image,
+}
+
+fn reference_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("reference")
+}
+
+fn out_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
@@ -0,0 +1,120 @@
+//! Renders each example the way it sets up its renderer, except for the surface.
+
+use golden::{Case, Tolerance, check};
+use renderer::{QUAD_INDICES, QUAD_VERTICES, ShaderDefValue, ShaderLibrary, wgpu};
+use std::borrow::Cow;
+
+#[path = "../../webgl-multi-canvas/src/scene.rs"]
+mod webgl_multi_canvas;
+
+fn wgsl(source: &str) -> wgpu::ShaderSource<'_> {
+    wgpu::ShaderSource::Wgsl(source.into())
+}
+
+#[test]
+fn basic() {
+    check(
+        Case {
+            name: "basic",
+            shader: wgsl(include_str!("../../basic/src/example.wgsl")),
+            vertices: QUAD_VERTICES,
+            indices: QUAD_INDICES,
+        },
+        Tolerance::default(),
+    );
+}
+
+#[test]
+fn composite_shader_monolithic() {
+    check(
+        Case {
+            name: "composite-shader",
+            shader: wgsl(include_str!("../../composite-shader/src/monolithic.wgsl")),
+            vertices: QUAD_VERTICES,
+            indices: QUAD_INDICES,
+        },
+        Tolerance::default(),
+    );
+}
+
+/// Composed shader must draw the same as the monolithic one.
+#[test]
+fn composite_shader_naga_oil() {
+    let mut library = ShaderLibrary::new();
+    library
+        .add_module(
+            "uniform.wgsl",
+            include_str!("../../composite-shader/src/uniform.wgsl"),
+        )
+        .unwrap();
+    library.add_shader(
+        "top.wgsl",
+        include_str!("../../composite-shader/src/top.wgsl"),
+    );
+    let defs = [("UNIFORM".to_owned(), ShaderDefValue::Bool(true))].into();
+    let naga_module = library.compose("top.wgsl", &defs).unwrap().clone();
+
+    check(
+        Case {
+            name: "composite-shader",
+            shader: wgpu::ShaderSource::Naga(Cow::Owned(naga_module)),
+            vertices: QUAD_VERTICES,
+            indices: QUAD_INDICES,
+        },
+        Tolerance::default(),
+    );
+}
+
+#[test]
+fn offscreen_on_worker() {
+    check(
+        Case {
+            name: "offscreen-on-worker",
+            shader: wgsl(include_str!("../../offscreen-on-worker/src/example.wgsl")),
+            vertices: QUAD_VERTICES,
+            indices: QUAD_INDICES,
+        },
+        Tolerance::default(),
+    );
+}
+
+#[test]
+fn webgl_multi_canvas() {
+    for (i, (vertices, indices)) in webgl_multi_canvas::meshes().iter().enumerate() {
+        check(
+            Case {
+                name: &format!("webgl-multi-canvas-{i}"),
+                shader: wgsl(include_str!("../../webgl-multi-canvas/src/example.wgsl")),
+                vertices,
+                indices,
+            },
+            Tolerance::default(),
+        );
+    }
+}
+
+#[test]
+fn webgpu_or_webgl() {
+    check(
+        Case {
+            name: "webgpu-or-webgl",
+            shader: wgsl(include_str!("../../webgpu-or-webgl/src/example.wgsl")),
+            vertices: QUAD_VERTICES,
+            indices: QUAD_INDICES,
+        },
+        Tolerance::default(),
+    );
+}
+
+#[test]
+fn wgpu_gl_surfaces() {
+    check(
+        Case {
+            name: "wgpu-gl-surfaces",
+            shader: wgsl(include_str!("../../wgpu-gl-surfaces/src/example.wgsl")),
+            vertices: QUAD_VERTICES,
+            indices: QUAD_INDICES,
+        },
+        Tolerance::default(),
+    );
+}
@@ -0,0 +1,80 @@
+//! Composes shaders sharing imports through a `ShaderLibrary`.
+
+use renderer::{Error, ShaderDefValue, ShaderDefs, ShaderLibrary};
+
+const UNIFORM: &str = include_str!("../../composite-shader/src/uniform.wgsl");
+const TOP: &str = include_str!("../../composite-shader/src/top.wgsl");
+
+/// Another shader importing `uniform::UniformData`.
+const TINT: &str = "
+#import uniform
+
+@group(0) @binding(0) var<uniform> uni: uniform::UniformData;
+
+@fragment
+fn f_main() -> @location(0) vec4<f32> {
+    return vec4f(fract(uni.time), 0.0, 0.0, 1.0);
+}
+";
+
+fn library() -> ShaderLibrary {
+    let mut library = ShaderLibrary::new();
+    library.add_module("uniform.wgsl", UNIFORM).unwrap();
+    library.add_shader("top.wgsl", TOP);
+    library.add_shader("tint.wgsl", TINT);
+    library
+}
+
+fn uniform_defs() -> ShaderDefs {
+    [("UNIFORM".to_owned(), ShaderDefValue::Bool(true))].into()
+}
+
+/// Shaders share the module, and each of them is composed once per set of defs.
+#[test]
+fn compose_shared_imports_once() {
+    let mut library = library();
+    let (defs, none) = (uniform_defs(), ShaderDefs::new());
+
+    let top = library.compose("top.wgsl", &defs).unwrap() as *const _;
+    assert!(library.compose("tint.wgsl", &none).is_ok());
+    assert!(library.is_cached("top.wgsl", &defs));
+    assert!(!library.is_cached("top.wgsl", &none));
+
+    // Without `UNIFORM`, `uni` isn't declared.
+    let bare = library.compose("top.wgsl", &none).unwrap();
+    assert!(bare.global_variables.is_empty());
+
+    // The cached one is given again.
+    assert_eq!(library.compose("top.wgsl", &defs).unwrap() as *const _, top);
+}
+
+/// Replacing a module composes cached shaders again, and an invalid one changes nothing.
+#[test]
+fn replace_module() {
+    let mut library = library();
+    let defs = uniform_defs();
+    library.compose("top.wgsl", &defs).unwrap();
+
+    let result = library.add_module("uniform.wgsl", "#define_import_path uniform\nstruct {");
+    assert!(matches!(result, Err(Error::Shader(_))), "{result:?}");
+    let broken = format!("{UNIFORM}\nfn f() -> i32 {{ return 1.0; }}");
+    let result = library.add_module("uniform.wgsl", &broken);
+    assert!(matches!(result, Err(Error::Shader(_))), "{result:?}");
+    assert!(library.compose("tint.wgsl", &ShaderDefs::new()).is_ok());
+
+    library.add_module("uniform.wgsl", UNIFORM).unwrap();
+    assert!(library.is_cached("top.wgsl", &defs));
+    assert!(library.is_cached("tint.wgsl", &ShaderDefs::new()));
+}
+
+/// Shaders that aren't added or don't compose are reported.
+#[test]
+fn compose_invalid_shader() {
+    let mut library = library();
+    let result = library.compose("missing.wgsl", &ShaderDefs::new());
+    assert!(matches!(result, Err(Error::Shader(_))), "{result:?}");
+
+    library.add_shader("broken.wgsl", "@fragment fn f_main( {");
+    let result = library.compose("broken.wgsl", &ShaderDefs::new());
+    assert!(matches!(result, Err(Error::Shader(_))), "{result:?}");
+}
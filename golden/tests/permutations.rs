@@ -0,0 +1,86 @@
+//! Validates every listed shader variant, so broken ones fail in CI rather than in the browser.
+
+use golden::{Case, Tolerance, capture, compare, create_renderer};
+use renderer::{
+    Define, Error, PermutationPipelines, Permutations, QUAD_INDICES, QUAD_VERTICES, ShaderDefValue,
+    ShaderDefs, wgpu,
+};
+
+#[path = "../../composite-shader/src/shaders.rs"]
+mod composite_shader;
+
+use composite_shader::{TOP, TOP_PERMUTATIONS, create_shader_library, top_defs};
+
+fn library() -> renderer::ShaderLibrary {
+    create_shader_library(
+        include_str!("../../composite-shader/src/top.wgsl"),
+        include_str!("../../composite-shader/src/uniform.wgsl"),
+    )
+    .unwrap()
+}
+
+/// All variants compose and validate. Run with `--nocapture` to see the listing.
+#[test]
+fn composite_shader_variants() {
+    let mut library = library();
+    TOP_PERMUTATIONS.validate(&mut library).unwrap();
+    print!("{TOP_PERMUTATIONS}");
+
+    for uniform in [false, true] {
+        assert!(TOP_PERMUTATIONS.find(&top_defs(uniform)).is_some());
+    }
+}
+
+/// The variant with `UNIFORM` picked while drawing looks the same as the composite-shader example.
+#[test]
+fn select_pipeline() {
+    let mut renderer = create_renderer(Case {
+        name: "composite-shader",
+        shader: wgpu::ShaderSource::Wgsl(include_str!("../../basic/src/example.wgsl").into()),
+        vertices: QUAD_VERTICES,
+        indices: QUAD_INDICES,
+    });
+    let mut library = library();
+    let pipelines = PermutationPipelines::new(&renderer, &mut library, &TOP_PERMUTATIONS).unwrap();
+    let pipelines = pollster::block_on(pipelines).unwrap();
+    assert_eq!(pipelines.pipelines.len(), TOP_PERMUTATIONS.variants.len());
+
+    let defs = [("UNIFORM".to_owned(), ShaderDefValue::Bool(true))].into();
+    renderer.render_pipeline = pipelines.select(&defs).unwrap().clone();
+    compare(
+        "composite-shader",
+        &capture(&mut renderer),
+        Tolerance::default(),
+    );
+
+    let unlisted = [("UNIFORM".to_owned(), ShaderDefValue::Bool(false))].into();
+    assert!(pipelines.select(&unlisted).is_none());
+}
+
+/// Variants using defines wrongly are rejected before composing them.
+#[test]
+fn invalid_variants() {
+    const DEFINES: &[Define] = &[Define::bool("UNIFORM"), Define::int("LEVEL", 0..=2)];
+    let variants: [&[&[(&str, ShaderDefValue)]]; 5] = [
+        &[&[("MISSING", ShaderDefValue::Bool(true))]],
+        &[&[("UNIFORM", ShaderDefValue::Int(1))]],
+        &[&[("LEVEL", ShaderDefValue::Int(3))]],
+        &[&[
+            ("LEVEL", ShaderDefValue::Int(1)),
+            ("LEVEL", ShaderDefValue::Int(2)),
+        ]],
+        &[&[], &[]],
+    ];
+
+    let mut library = library();
+    for variants in variants {
+        let permutations = Permutations {
+            shader: TOP,
+            defines: DEFINES,
+            variants,
+        };
+        let result = permutations.validate(&mut library);
+        assert!(matches!(result, Err(Error::Shader(_))), "{result:?}");
+        assert!(!library.is_cached(TOP, &ShaderDefs::new()));
+    }
+}
@@ -0,0 +1,49 @@
+//! Reloads shaders the way the examples' dev mode does.
+
+use golden::{Case, Tolerance, capture, compare, create_renderer};
+use renderer::{Error, QUAD_INDICES, QUAD_VERTICES, wgpu};
+
+fn wgsl(source: &str) -> wgpu::ShaderSource<'_> {
+    wgpu::ShaderSource::Wgsl(source.into())
+}
+
+fn basic() -> Case<'static> {
+    Case {
+        name: "basic",
+        shader: wgsl(include_str!("../../basic/src/example.wgsl")),
+        vertices: QUAD_VERTICES,
+        indices: QUAD_INDICES,
+    }
+}
+
+/// A valid source replaces the pipeline.
+#[test]
+fn reload_valid_shader() {
+    let mut renderer = create_renderer(basic());
+    let source = wgsl(include_str!("../../offscreen-on-worker/src/example.wgsl"));
+    renderer.render_pipeline = pollster::block_on(renderer.create_pipeline(source)).unwrap();
+
+    compare(
+        "offscreen-on-worker",
+        &capture(&mut renderer),
+        Tolerance::default(),
+    );
+}
+
+/// An invalid source is reported, and the last good pipeline keeps drawing.
+#[test]
+fn reload_invalid_shader() {
+    let mut renderer = create_renderer(basic());
+    let sources = [
+        // Doesn't parse.
+        "@vertex fn v_main(",
+        // Parses, but has no `f_main`.
+        "@vertex fn v_main() -> @builtin(position) vec4f { return vec4f(); }",
+    ];
+    for source in sources {
+        let result = pollster::block_on(renderer.create_pipeline(wgsl(source)));
+        assert!(matches!(result, Err(Error::Shader(_))), "{result:?}");
+    }
+
+    compare("basic", &capture(&mut renderer), Tolerance::default());
+}
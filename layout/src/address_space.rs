@@ -0,0 +1,419 @@
+use crate::{
+    Member, MemberDiff,
+    diff::{differing_members, hex_dump},
+    validate::{init_buffer_type, layout_of, parse},
+};
+use core::{fmt, mem::ManuallyDrop, ptr, slice};
+use my_wgsl::WgslCompatible;
+use std::error::Error;
+use wgpu::util::DeviceExt;
+
+/// `wgpu::Device` and `wgpu::Queue` the tests run on.
+#[derive(Debug)]
+pub struct Gpu {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl Gpu {
+    /// Requests an adapter and then a device and a queue from it. The device has
+    /// [`wgpu::Features::SHADER_F16`] if the adapter supports it.
+    ///
+    /// * backends - e.g. [`wgpu::Backends::BROWSER_WEBGPU`]
+    pub async fn new(backends: wgpu::Backends) -> Self {
+        // Creates a `wgpu::Instance`, `wgpu::Adapter`, `wgpu::Device`, and `wgpu::Queue`.
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+        let adapter = wgpu::util::initialize_adapter_from_env_or_default(&instance, None)
+            .await
+            .unwrap();
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                // f16 cases need this. They fail without it.
+                required_features: adapter.features() & wgpu::Features::SHADER_F16,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        Self { device, queue }
+    }
+}
+
+/// Address space the GPU reads the data from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpace {
+    /// `var<uniform>`
+    Uniform,
+    /// `var<storage, read>`
+    Storage,
+}
+
+/// Why the GPU couldn't read the data as we sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The GPU rejected the WGSL type or the buffer. The message is what the browser or wgpu
+    /// reported.
+    Validation(String),
+    /// The GPU read the data, but it wasn't the same as what we sent.
+    Mismatch {
+        /// `Debug` output of the data we sent.
+        sent: String,
+        /// `Debug` output of the data the GPU copied back.
+        echoed: String,
+        /// Bytes of the data we sent.
+        sent_bytes: Vec<u8>,
+        /// Bytes the GPU copied back, as many as `sent_bytes`.
+        echoed_bytes: Vec<u8>,
+        /// Members whose bytes differ, with their offsets in WGSL. Named as the Rust fields. Empty
+        /// if the type is not a struct.
+        members: Vec<Member>,
+    },
+    /// The GPU read a runtime-sized array, but an element of it, or a member before it, wasn't the
+    /// same as what we sent.
+    ElementMismatch {
+        /// Index of the first differing element. `None` if a member before the array differs.
+        index: Option<usize>,
+        /// Rust field whose bytes differ first. `None` if the element is not a struct.
+        field: Option<String>,
+        /// `Debug` output of the element, or the members before the array, we sent.
+        sent: String,
+        /// `Debug` output of what the GPU copied back.
+        echoed: String,
+    },
+    /// WGSL lays out the type differently from Rust. Only
+    /// [`validate_layout`](crate::validate_layout) tells this, because the GPU doesn't know the
+    /// Rust type.
+    Layout {
+        wgsl_size: usize,
+        rust_size: usize,
+        /// Members at different offsets or of different sizes.
+        members: Vec<MemberDiff>,
+    },
+    /// The arguments can't be tested, e.g. an empty slice, or a WGSL type that doesn't end in a
+    /// runtime-sized array.
+    InvalidArgument(String),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Validation(message) => write!(f, "validation error: {message}"),
+            Self::Mismatch {
+                sent,
+                echoed,
+                sent_bytes,
+                echoed_bytes,
+                members,
+            } => {
+                write!(f, "data mismatch: sent: {sent}, echoed: {echoed}")?;
+                for member in members {
+                    write!(f, "\n  `{}`: {member}", member.name)?;
+                }
+                write!(f, "\n{}", hex_dump(sent_bytes, echoed_bytes))
+            }
+            Self::ElementMismatch {
+                index,
+                field,
+                sent,
+                echoed,
+            } => {
+                match index {
+                    Some(index) => write!(f, "element mismatch at [{index}]")?,
+                    None => write!(f, "mismatch before the array")?,
+                }
+                if let Some(field) = field {
+                    write!(f, " in `{field}`")?;
+                }
+                write!(f, ": sent: {sent}, echoed: {echoed}")
+            }
+            Self::Layout {
+                wgsl_size,
+                rust_size,
+                members,
+            } => {
+                write!(
+                    f,
+                    "layout mismatch: WGSL size {wgsl_size}, Rust size {rust_size}"
+                )?;
+                for member in members {
+                    write!(f, "\n  {member}")?;
+                }
+                Ok(())
+            }
+            Self::InvalidArgument(message) => write!(f, "invalid argument: {message}"),
+        }
+    }
+}
+
+impl Error for LayoutError {}
+
+pub type TestResult = Result<(), LayoutError>;
+
+/// Tests if the gpu can read the given data correctly via the given address space.
+///
+/// `T::WGSL_DEFINE` goes into the shader, but definitions of the structs `T` contains don't. Pass
+/// them in `dependencies`. e.g. `&[A::WGSL_DEFINE]` for `struct B { b0: A }`
+///
+/// * gpu - A reference to a [`Gpu`].
+/// * address_space - Where the gpu reads the data from.
+/// * data - The data that will be sent to GPU.
+/// * dependencies - Type definitions `T` depends on.
+pub async fn test_address_space<T: WgslCompatible + fmt::Debug + PartialEq + 'static>(
+    gpu: &Gpu,
+    address_space: AddressSpace,
+    data: T,
+    dependencies: &[&str],
+) -> TestResult {
+    let define = dependencies
+        .iter()
+        .copied()
+        .chain([T::WGSL_DEFINE])
+        .collect::<Vec<_>>()
+        .join("\n");
+    test_address_space_with(gpu, address_space, data, Some(&define), T::WGSL_IDENT).await
+}
+
+/// Same as [`test_address_space`], but with a hand-written WGSL type. Useful for types that
+/// `WgslCompatible` refuses to derive.
+///
+/// * gpu - A reference to a [`Gpu`].
+/// * address_space - Where the gpu reads the data from.
+/// * data - The data that will be sent to GPU.
+/// * wgsl_define_type - Type definition in WGSL source code. e.g. "struct A { .. }"
+/// * wgsl_type - WGSL type of the given `data`.
+pub async fn test_address_space_with<T: fmt::Debug + PartialEq + 'static>(
+    gpu: &Gpu,
+    address_space: AddressSpace,
+    data: T,
+    wgsl_define_type: Option<&str>,
+    wgsl_type: &str,
+) -> TestResult {
+    let ptr = &data as *const T as *const u8;
+    let contents: &[u8] = unsafe { slice::from_raw_parts(ptr, size_of::<T>()) };
+
+    let shader_code = echo_shader(address_space, wgsl_define_type, wgsl_type);
+    let echoed = echo_on_gpu(gpu, address_space, contents, &shader_code).await?;
+
+    // Tests if the echoed bytes contain the data we put in. They may not be aligned for `T`. And
+    // the copy must not be dropped, because it shares resources with `data` if there are any.
+    let read = ManuallyDrop::new(unsafe { ptr::read_unaligned(echoed.as_ptr() as *const T) });
+    if *read == data {
+        return Ok(());
+    }
+
+    // Finds members that differ with WGSL offsets. The GPU has accepted the shader, but naga may
+    // not in the browser.
+    let echoed = &echoed[..contents.len()];
+    let members = match parse(&shader_code) {
+        Ok(module) => {
+            let mut layouter = naga::proc::Layouter::default();
+            layouter.update(module.to_ctx()).unwrap();
+            let ty = init_buffer_type(&module);
+            let wgsl = layout_of(&module, &layouter, ty, address_space);
+            differing_members(&wgsl.members, contents, echoed).collect()
+        }
+        Err(_) => Vec::new(),
+    };
+
+    Err(LayoutError::Mismatch {
+        sent: format!("{data:?}"),
+        echoed: format!("{:?}", *read),
+        sent_bytes: contents.to_vec(),
+        echoed_bytes: echoed.to_vec(),
+        members,
+    })
+}
+
+/// Returns a compute shader that copies `wgsl_type` data from the given address space to a storage
+/// buffer.
+pub(crate) fn echo_shader(
+    address_space: AddressSpace,
+    wgsl_define_type: Option<&str>,
+    wgsl_type: &str,
+) -> String {
+    // Runtime-sized arrays can't be assigned at once.
+    let copy = if is_runtime_array(wgsl_type) {
+        copy_array("")
+    } else {
+        "echo_buffer = init_buffer;".to_owned()
+    };
+    echo_shader_copying(address_space, wgsl_define_type, wgsl_type, &copy)
+}
+
+/// Returns statements copying `init_buffer{path}`, a runtime-sized array, element by element.
+pub(crate) fn copy_array(path: &str) -> String {
+    format!(
+        "for (var i = 0u; i < arrayLength(&init_buffer{path}); i++) {{
+            echo_buffer{path}[i] = init_buffer{path}[i];
+        }}"
+    )
+}
+
+/// Same as [`echo_shader`], but copies `init_buffer` to `echo_buffer` with the given statements.
+pub(crate) fn echo_shader_copying(
+    address_space: AddressSpace,
+    wgsl_define_type: Option<&str>,
+    wgsl_type: &str,
+    copy: &str,
+) -> String {
+    let var = match address_space {
+        AddressSpace::Uniform => "var<uniform>",
+        AddressSpace::Storage => "var<storage, read>",
+    };
+    let wgsl_define_type = wgsl_define_type.unwrap_or_default();
+    format!(
+        "
+        {wgsl_define_type}
+
+        @group(0) @binding(0) {var} init_buffer: {wgsl_type};
+        @group(0) @binding(1) var<storage, read_write> echo_buffer: {wgsl_type};
+
+        @compute @workgroup_size(1)
+        fn c_main() {{
+            {copy}
+        }}
+    "
+    )
+}
+
+/// Tells if the WGSL type is a runtime-sized array such as `array<T>`.
+pub(crate) fn is_runtime_array(wgsl_type: &str) -> bool {
+    let Some(element) = wgsl_type
+        .trim()
+        .strip_prefix("array")
+        .and_then(|rest| rest.trim_start().strip_prefix('<'))
+        .and_then(|rest| rest.strip_suffix('>'))
+    else {
+        return false;
+    };
+
+    // Commas in nested types like `array<array<f32, 4>>` don't count.
+    let mut depth = 0;
+    !element.chars().any(|c| {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            _ => {}
+        }
+        c == ',' && depth == 0
+    })
+}
+
+/// Runs the shader copying `init_buffer` to `echo_buffer` and returns what `echo_buffer` has got.
+///
+/// * contents - Initial data of `init_buffer`.
+pub(crate) async fn echo_on_gpu(
+    gpu: &Gpu,
+    address_space: AddressSpace,
+    contents: &[u8],
+    shader_code: &str,
+) -> Result<Vec<u8>, LayoutError> {
+    let Gpu { device, queue } = gpu;
+
+    let usage = match address_space {
+        AddressSpace::Uniform => wgpu::BufferUsages::UNIFORM,
+        AddressSpace::Storage => wgpu::BufferUsages::STORAGE,
+    };
+
+    // Buffer creation is validated as well.
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+    // Creates a `wgpu::Buffer` for writing some data to the gpu.
+    let init_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Init buffer"),
+        contents,
+        usage,
+    });
+
+    // Creates a `wgpu::Buffer` for copying the data on the gpu.
+    let echo_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Echo buffer"),
+        size: init_buffer.size(),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+
+    // Creates a `wgpu::Buffer` for reading the copied data from the gpu.
+    let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Read buffer"),
+        size: init_buffer.size(),
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    // Creates a `wgpu::ShaderModule`.
+    let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(shader_code.into()),
+    });
+
+    // Creates a `wgpu::ComputePipeline`.
+    let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Compute Pipeline"),
+        layout: None,
+        module: &shader_module,
+        entry_point: Some("c_main"),
+        compilation_options: Default::default(),
+        cache: None,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Bind group"),
+        layout: &compute_pipeline.get_bind_group_layout(0),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: init_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: echo_buffer.as_entire_binding(),
+            },
+        ],
+    });
+
+    // Layout errors show up while creating the shader module, the pipeline or the bind group.
+    if let Some(error) = device.pop_error_scope().await {
+        // `Display` of the error is just a title.
+        let message = match error {
+            wgpu::Error::Validation { description, .. } => description,
+            error => error.to_string(),
+        };
+        return Err(LayoutError::Validation(message));
+    }
+
+    // Creates a `wgpu::CommandEncoder`.
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Compute command encoder"),
+    });
+
+    // Writes a compute pass.
+    let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some("Compute pass"),
+        timestamp_writes: None,
+    });
+    compute_pass.set_pipeline(&compute_pipeline);
+    compute_pass.set_bind_group(0, Some(&bind_group), &[]);
+    compute_pass.dispatch_workgroups(1, 1, 1);
+    drop(compute_pass);
+
+    // Copies the computation result.
+    encoder.copy_buffer_to_buffer(&echo_buffer, 0, &read_buffer, 0, None);
+
+    // Submits the command to the queue.
+    queue.submit(std::iter::once(encoder.finish()));
+
+    // Maps the read buffer. Browsers map it on their own, but native backends need polling.
+    let (tx, rx) = futures::channel::oneshot::channel();
+    read_buffer.map_async(wgpu::MapMode::Read, .., move |result| {
+        let _ = tx.send(result);
+    });
+    device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+    rx.await.unwrap().unwrap();
+
+    let echoed = read_buffer.get_mapped_range(..).to_vec();
+    read_buffer.unmap();
+    Ok(echoed)
+}
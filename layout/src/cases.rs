@@ -0,0 +1,572 @@
+//! Test cases about layout requirements. Each requirement has a failing case and a workaround
+//! case. They run in the browser through [`test`](crate::test) and natively through
+//! `cargo test-native`. Cases ending with `_on_cpu` check the same with naga instead of the GPU.
+
+/// # What layout rule is this about?
+///
+/// 'uniform' storage requires that the number of bytes between the start of the previous member of
+/// type struct and the current member be a multiple of 16 bytes.
+#[rustfmt::skip]
+pub mod uniform_offset {
+    use crate::*;
+
+    pub async fn fail(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct A { a0: i32 }
+
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct B { b0: A, b1: i32 }
+
+        let define = r"
+            struct A { a0: i32 }
+            struct B {
+                b0: A,
+                b1: i32 // Error occurs here. The previous member is of type struct, so we need at
+                        // least 16 bytes between the starts of `b0` and `b1`.
+            }
+        ";
+
+        let data = B {
+            b0: A { a0: 1 },
+            b1: 2,
+        };
+        test_address_space_with(gpu, AddressSpace::Uniform, data, Some(define), "B").await
+    }
+
+    // naga finds the same error without the GPU.
+    pub fn fail_on_cpu() -> TestResult {
+        #[repr(C)]
+        struct A { a0: i32 }
+
+        #[repr(C)]
+        struct B { b0: A, b1: i32 }
+
+        let define = r"
+            struct A { a0: i32 }
+            struct B { b0: A, b1: i32 }
+        ";
+
+        validate_layout_with(AddressSpace::Uniform, &rust_layout!(B { b0, b1 }), Some(define), "B")
+    }
+
+    // `WgslCompatible` let us know where we should fix to make our structs to be compatible with
+    // WGSL.
+    pub async fn ok(gpu: &Gpu) -> TestResult {
+        #[derive(WgslCompatible, Debug, PartialEq)]
+        #[repr(C)]
+        struct A { a0: i32, pad: [u8; 12] }
+
+        #[derive(WgslCompatible, Debug, PartialEq)]
+        #[wgsl(uniform)]
+        #[repr(C)]
+        struct B { b0: A, b1: i32 }
+
+        assert_wgsl_eq(
+            &format!("{}{}", A::WGSL_DEFINE, B::WGSL_DEFINE),
+            "struct A { @size(16) a0: i32 }
+            struct B { b0: A, b1: i32 }"
+        );
+
+        let data = B {
+            b0: A { a0: 1, pad: [0; _] },
+            b1: 2,
+        };
+        test_address_space(gpu, AddressSpace::Uniform, data, &[A::WGSL_DEFINE]).await
+    }
+
+    pub fn ok_on_cpu() -> TestResult {
+        #[derive(WgslCompatible)]
+        #[repr(C)]
+        struct A { a0: i32, pad: [u8; 12] }
+
+        #[derive(WgslCompatible)]
+        #[wgsl(uniform)]
+        #[repr(C)]
+        struct B { b0: A, b1: i32 }
+
+        validate_layout::<B>(AddressSpace::Uniform, &rust_layout!(B { b0, b1 }), &[A::WGSL_DEFINE])
+    }
+}
+
+/// # What layout rule is this about?
+///
+/// `vec3<T>` is 12 bytes, but aligned to 16 bytes. Rust arrays like `[f32; 3]` are aligned to their
+/// elements, so a member following 4 bytes of data starts at different offsets.
+#[rustfmt::skip]
+pub mod vec3_align {
+    use crate::*;
+    use my_wgsl::Vec3f;
+
+    pub async fn fail(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct A { a0: f32, a1: [f32; 3] }
+
+        let define = r"
+            struct A {
+                a0: f32,
+                a1: vec3f // This starts at offset 16, so `A` is 32 bytes. Browsers tell us that
+                          // the buffer, 16 bytes, is smaller than the minimum binding size.
+            }
+        ";
+
+        let data = A { a0: 1.0, a1: [2.0, 3.0, 4.0] };
+        test_address_space_with(gpu, AddressSpace::Storage, data, Some(define), "A").await
+    }
+
+    pub async fn ok(gpu: &Gpu) -> TestResult {
+        #[derive(WgslCompatible, Debug, PartialEq)]
+        #[repr(C, align(16))]
+        struct A { a0: f32, pad: [u8; 12], a1: Vec3f }
+
+        // WGSL aligns `a1` on its own, so `pad` doesn't show up.
+        assert_wgsl_eq(A::WGSL_DEFINE, "struct A { a0: f32, a1: vec3f }");
+
+        let data = A { a0: 1.0, pad: [0; _], a1: Vec3f::new(2.0, 3.0, 4.0) };
+        test_address_space(gpu, AddressSpace::Storage, data, &[]).await
+    }
+
+    pub fn fail_on_cpu() -> TestResult {
+        #[repr(C)]
+        struct A { a0: f32, a1: [f32; 3] }
+
+        let define = "struct A { a0: f32, a1: vec3f }";
+        validate_layout_with(AddressSpace::Storage, &rust_layout!(A { a0, a1 }), Some(define), "A")
+    }
+
+    pub fn ok_on_cpu() -> TestResult {
+        #[derive(WgslCompatible)]
+        #[repr(C, align(16))]
+        struct A { a0: f32, pad: [u8; 12], a1: Vec3f }
+
+        validate_layout::<A>(AddressSpace::Storage, &rust_layout!(A { a0, a1 }), &[])
+    }
+}
+
+/// # What layout rule is this about?
+///
+/// 'uniform' storage requires that the stride of `array<T, N>` be a multiple of 16 bytes.
+#[rustfmt::skip]
+pub mod uniform_array_stride {
+    use crate::*;
+
+    pub async fn fail(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct A { a0: [f32; 4] }
+
+        let define = r"
+            struct A {
+                a0: array<f32, 4> // Error occurs here. The stride is 4 bytes. Browsers suggest
+                                  // using a vector or a struct as the element type instead.
+            }
+        ";
+
+        let data = A { a0: [1.0, 2.0, 3.0, 4.0] };
+        test_address_space_with(gpu, AddressSpace::Uniform, data, Some(define), "A").await
+    }
+
+    // Wraps each element in a 16 bytes struct.
+    pub async fn ok(gpu: &Gpu) -> TestResult {
+        #[derive(WgslCompatible, Debug, PartialEq)]
+        #[repr(C)]
+        struct E { e0: f32, pad: [u8; 12] }
+
+        #[derive(WgslCompatible, Debug, PartialEq)]
+        #[wgsl(uniform)]
+        #[repr(C)]
+        struct A { a0: [E; 4] }
+
+        assert_wgsl_eq(
+            &format!("{}{}", E::WGSL_DEFINE, A::WGSL_DEFINE),
+            "struct E { @size(16) e0: f32 }
+            struct A { a0: array<E, 4> }"
+        );
+
+        let e = |e0| E { e0, pad: [0; _] };
+        let data = A { a0: [e(1.0), e(2.0), e(3.0), e(4.0)] };
+        test_address_space(gpu, AddressSpace::Uniform, data, &[E::WGSL_DEFINE]).await
+    }
+
+    pub fn fail_on_cpu() -> TestResult {
+        #[repr(C)]
+        struct A { a0: [f32; 4] }
+
+        let define = "struct A { a0: array<f32, 4> }";
+        validate_layout_with(AddressSpace::Uniform, &rust_layout!(A { a0 }), Some(define), "A")
+    }
+
+    pub fn ok_on_cpu() -> TestResult {
+        #[derive(WgslCompatible)]
+        #[repr(C)]
+        struct E { e0: f32, pad: [u8; 12] }
+
+        #[derive(WgslCompatible)]
+        #[wgsl(uniform)]
+        #[repr(C)]
+        struct A { a0: [E; 4] }
+
+        validate_layout::<A>(AddressSpace::Uniform, &rust_layout!(A { a0 }), &[E::WGSL_DEFINE])
+    }
+}
+
+/// # What layout rule is this about?
+///
+/// Size of a struct is rounded up to its alignment, which is the largest alignment of its members.
+/// Rust does the same, but WGSL types like `vec2f` are aligned more than their Rust counterparts,
+/// so a member following a nested struct starts at different offsets.
+#[rustfmt::skip]
+pub mod struct_size {
+    use crate::*;
+    use my_wgsl::Vec2f;
+
+    pub async fn fail(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct A { a0: [f32; 2], a1: f32 }
+
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct B { b0: A, b1: f32 }
+
+        let define = r"
+            struct A { a0: vec2f, a1: f32 } // 12 bytes of data, but 16 bytes in size.
+            struct B {
+                b0: A,
+                b1: f32 // This starts at offset 16, so `B` is 24 bytes. Browsers tell us that
+                        // the buffer, 16 bytes, is smaller than the minimum binding size.
+            }
+        ";
+
+        let data = B { b0: A { a0: [1.0, 2.0], a1: 3.0 }, b1: 4.0 };
+        test_address_space_with(gpu, AddressSpace::Storage, data, Some(define), "B").await
+    }
+
+    // Aligns `A` as WGSL does.
+    pub async fn ok(gpu: &Gpu) -> TestResult {
+        #[derive(WgslCompatible, Debug, PartialEq)]
+        #[repr(C, align(8))]
+        struct A { a0: Vec2f, a1: f32 }
+
+        #[derive(WgslCompatible, Debug, PartialEq)]
+        #[repr(C)]
+        struct B { b0: A, b1: f32 }
+
+        assert_wgsl_eq(
+            &format!("{}{}", A::WGSL_DEFINE, B::WGSL_DEFINE),
+            "struct A { a0: vec2f, a1: f32 }
+            struct B { b0: A, b1: f32 }"
+        );
+
+        let data = B { b0: A { a0: Vec2f::new(1.0, 2.0), a1: 3.0 }, b1: 4.0 };
+        test_address_space(gpu, AddressSpace::Storage, data, &[A::WGSL_DEFINE]).await
+    }
+
+    pub fn fail_on_cpu() -> TestResult {
+        #[repr(C)]
+        struct A { a0: [f32; 2], a1: f32 }
+
+        #[repr(C)]
+        struct B { b0: A, b1: f32 }
+
+        let define = r"
+            struct A { a0: vec2f, a1: f32 }
+            struct B { b0: A, b1: f32 }
+        ";
+        validate_layout_with(AddressSpace::Storage, &rust_layout!(B { b0, b1 }), Some(define), "B")
+    }
+
+    pub fn ok_on_cpu() -> TestResult {
+        #[derive(WgslCompatible)]
+        #[repr(C, align(8))]
+        struct A { a0: Vec2f, a1: f32 }
+
+        #[derive(WgslCompatible)]
+        #[repr(C)]
+        struct B { b0: A, b1: f32 }
+
+        validate_layout::<B>(AddressSpace::Storage, &rust_layout!(B { b0, b1 }), &[A::WGSL_DEFINE])
+    }
+}
+
+/// # What layout rule is this about?
+///
+/// Runtime-sized arrays, `array<T>`, are allowed only in 'storage' address space. See
+/// [`struct_fail`](runtime_array::struct_fail) for structs ending in them.
+#[rustfmt::skip]
+pub mod runtime_array {
+    use crate::*;
+
+    pub async fn fail(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct P { pos: [f32; 3], life: f32 }
+
+        let define = "struct P { pos: vec3f, life: f32 }";
+
+        let data = [
+            P { pos: [1.0, 2.0, 3.0], life: 4.0 },
+            P { pos: [5.0, 6.0, 7.0], life: 8.0 },
+        ];
+        // Error occurs here. Browsers tell us that `array<P>` isn't constructible and can't be in
+        // 'uniform' address space.
+        test_address_space_with(gpu, AddressSpace::Uniform, data, Some(define), "array<P>").await
+    }
+
+    pub async fn ok(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct P { pos: [f32; 3], life: f32 }
+
+        let define = "struct P { pos: vec3f, life: f32 }";
+
+        let data = [
+            P { pos: [1.0, 2.0, 3.0], life: 4.0 },
+            P { pos: [5.0, 6.0, 7.0], life: 8.0 },
+        ];
+        test_runtime_array_with(gpu, &data, Some(define), "P").await
+    }
+
+    // A struct may end in a runtime-sized array, and the array starts at a multiple of its element
+    // alignment. `P` is aligned to 16 bytes in WGSL, but 4 bytes in Rust.
+    pub async fn struct_fail(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct Head { count: u32 }
+
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct P { pos: [f32; 3], life: f32 }
+
+        let define = r"
+            struct P { pos: vec3f, life: f32 }
+            struct Particles {
+                count: u32,
+                items: array<P> // Starts at offset 16, but Rust puts the elements at offset 4.
+            }
+        ";
+
+        let items = [
+            P { pos: [1.0, 2.0, 3.0], life: 4.0 },
+            P { pos: [5.0, 6.0, 7.0], life: 8.0 },
+        ];
+        test_runtime_array_struct_with(gpu, &Head { count: 2 }, &items, define, "Particles").await
+    }
+
+    pub async fn struct_ok(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C, align(16))]
+        struct Head { count: u32 }
+
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct P { pos: [f32; 3], life: f32 }
+
+        let define = r"
+            struct P { pos: vec3f, life: f32 }
+            struct Particles { count: u32, items: array<P> }
+        ";
+
+        let items = [
+            P { pos: [1.0, 2.0, 3.0], life: 4.0 },
+            P { pos: [5.0, 6.0, 7.0], life: 8.0 },
+        ];
+        test_runtime_array_struct_with(gpu, &Head { count: 2 }, &items, define, "Particles").await
+    }
+
+    pub fn fail_on_cpu() -> TestResult {
+        #[repr(C)]
+        struct P { pos: [f32; 3], life: f32 }
+
+        let define = "struct P { pos: vec3f, life: f32 }";
+        let rust = rust_layout!(P { pos, life });
+        validate_layout_with(AddressSpace::Uniform, &rust, Some(define), "array<P>")
+    }
+
+    pub fn ok_on_cpu() -> TestResult {
+        #[repr(C)]
+        struct P { pos: [f32; 3], life: f32 }
+
+        let define = "struct P { pos: vec3f, life: f32 }";
+        let rust = rust_layout!(P { pos, life });
+        validate_layout_with(AddressSpace::Storage, &rust, Some(define), "array<P>")
+    }
+}
+
+/// # What layout rule is this about?
+///
+/// `@align(n)` and `@size(n)` move members in WGSL. Rust structs need explicit padding fields to
+/// follow them.
+#[rustfmt::skip]
+pub mod align_size_attributes {
+    use crate::*;
+
+    const DEFINE: &str = r"
+        struct A {
+            @size(8) a0: f32,
+            @align(8) a1: f32, // Starts at offset 8.
+            a2: f32
+        }
+    ";
+
+    // Browsers accept this, but read `a1` from `a2` and `a2` from the padding at the end.
+    pub async fn fail(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C, align(16))]
+        struct A { a0: f32, a1: f32, a2: f32 }
+
+        let data = A { a0: 1.0, a1: 2.0, a2: 3.0 };
+        test_address_space_with(gpu, AddressSpace::Storage, data, Some(DEFINE), "A").await
+    }
+
+    pub async fn ok(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C, align(16))]
+        struct A { a0: f32, pad: [u8; 4], a1: f32, a2: f32 }
+
+        let data = A { a0: 1.0, pad: [0; _], a1: 2.0, a2: 3.0 };
+        test_address_space_with(gpu, AddressSpace::Storage, data, Some(DEFINE), "A").await
+    }
+
+    pub fn fail_on_cpu() -> TestResult {
+        #[repr(C, align(16))]
+        struct A { a0: f32, a1: f32, a2: f32 }
+
+        let rust = rust_layout!(A { a0, a1, a2 });
+        validate_layout_with(AddressSpace::Storage, &rust, Some(DEFINE), "A")
+    }
+
+    pub fn ok_on_cpu() -> TestResult {
+        #[repr(C, align(16))]
+        struct A { a0: f32, pad: [u8; 4], a1: f32, a2: f32 }
+
+        let rust = rust_layout!(A { a0, a1, a2 });
+        validate_layout_with(AddressSpace::Storage, &rust, Some(DEFINE), "A")
+    }
+}
+
+/// # What layout rule is this about?
+///
+/// `matCxR<T>` is laid out as an array of `C` column vectors, `vecR<T>`. So columns of
+/// `mat3x3<f32>` are 16 bytes apart, and the matrix is 48 bytes.
+#[rustfmt::skip]
+pub mod mat3x3 {
+    use crate::*;
+    use my_wgsl::{Mat3x3f, Vec3f};
+
+    pub async fn fail(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct A { a0: [[f32; 3]; 3] }
+
+        let define = r"
+            struct A {
+                a0: mat3x3f // 48 bytes. Browsers tell us that the buffer, 36 bytes, is smaller
+                            // than the minimum binding size.
+            }
+        ";
+
+        let data = A { a0: [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]] };
+        test_address_space_with(gpu, AddressSpace::Storage, data, Some(define), "A").await
+    }
+
+    // `Mat3x3f` has padded columns.
+    pub async fn ok(gpu: &Gpu) -> TestResult {
+        #[derive(WgslCompatible, Debug, PartialEq)]
+        #[repr(C)]
+        struct A { a0: Mat3x3f }
+
+        assert_wgsl_eq(A::WGSL_DEFINE, "struct A { a0: mat3x3f }");
+
+        let data = A {
+            a0: Mat3x3f::new(
+                Vec3f::new(1.0, 2.0, 3.0),
+                Vec3f::new(4.0, 5.0, 6.0),
+                Vec3f::new(7.0, 8.0, 9.0),
+            ),
+        };
+        test_address_space(gpu, AddressSpace::Storage, data, &[]).await
+    }
+
+    pub fn fail_on_cpu() -> TestResult {
+        #[repr(C)]
+        struct A { a0: [[f32; 3]; 3] }
+
+        let define = "struct A { a0: mat3x3f }";
+        validate_layout_with(AddressSpace::Storage, &rust_layout!(A { a0 }), Some(define), "A")
+    }
+
+    pub fn ok_on_cpu() -> TestResult {
+        #[derive(WgslCompatible)]
+        #[repr(C)]
+        struct A { a0: Mat3x3f }
+
+        validate_layout::<A>(AddressSpace::Storage, &rust_layout!(A { a0 }), &[])
+    }
+}
+
+/// # What layout rule is this about?
+///
+/// `f16` is 2 bytes in size and alignment, but shaders must enable it with `enable f16;`, and the
+/// device must have `shader-f16` feature. Rust doesn't have stable `f16` yet, so we send the bits
+/// in `u16`.
+#[rustfmt::skip]
+pub mod f16 {
+    use crate::*;
+
+    const ONE: u16 = 0x3C00;
+    const TWO: u16 = 0x4000;
+    const THREE: u16 = 0x4200;
+
+    pub async fn fail(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct A { a0: u16, a1: u16, a2: f32 }
+
+        let define = r"
+            struct A {
+                a0: f16, // Error occurs here. Browsers tell us to enable f16 extension.
+                a1: f16,
+                a2: f32
+            }
+        ";
+
+        let data = A { a0: ONE, a1: TWO, a2: 3.0 };
+        test_address_space_with(gpu, AddressSpace::Storage, data, Some(define), "A").await
+    }
+
+    pub async fn ok(gpu: &Gpu) -> TestResult {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct A { a0: u16, a1: u16, a2: f32, a3: u16 }
+
+        // `enable` must come first in the shader.
+        let define = r"
+            enable f16;
+            struct A { a0: f16, a1: f16, a2: f32, a3: f16 }
+        ";
+
+        let data = A { a0: ONE, a1: TWO, a2: 3.0, a3: THREE };
+        test_address_space_with(gpu, AddressSpace::Storage, data, Some(define), "A").await
+    }
+
+    pub fn fail_on_cpu() -> TestResult {
+        #[repr(C)]
+        struct A { a0: u16, a1: u16, a2: f32 }
+
+        let define = "struct A { a0: f16, a1: f16, a2: f32 }";
+        let rust = rust_layout!(A { a0, a1, a2 });
+        validate_layout_with(AddressSpace::Storage, &rust, Some(define), "A")
+    }
+
+    pub fn ok_on_cpu() -> TestResult {
+        #[repr(C)]
+        struct A { a0: u16, a1: u16, a2: f32, a3: u16 }
+
+        let define = "enable f16; struct A { a0: f16, a1: f16, a2: f32, a3: f16 }";
+        let rust = rust_layout!(A { a0, a1, a2, a3 });
+        validate_layout_with(AddressSpace::Storage, &rust, Some(define), "A")
+    }
+}
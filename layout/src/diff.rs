@@ -0,0 +1,89 @@
+//! Finds the members whose bytes the GPU didn't echo as we sent.
+//!
+//! Offsets of the members come from naga's layout of the WGSL type, not from
+//! [`WgslCompatible`](my_wgsl::WgslCompatible)'s metadata. The derive adds `@align` and `@size`
+//! so that its WGSL offsets are the same as Rust's, so they can't tell where the GPU put a member.
+//! What the GPU does is lay out the generated WGSL source, which naga does by the same rules.
+//!
+//! Members are named after WGSL, which the derive takes from the Rust fields. Only fields of tuple
+//! structs differ, e.g. `0` is `_0` in WGSL, so they're reported as `0`.
+
+use crate::Member;
+use core::fmt::Write;
+
+/// Returns the members whose bytes differ, named as the Rust fields.
+pub(crate) fn differing_members<'a>(
+    members: &'a [Member],
+    sent: &'a [u8],
+    echoed: &'a [u8],
+) -> impl Iterator<Item = Member> + 'a {
+    members
+        .iter()
+        .filter(|member| {
+            let range = member.offset..member.offset + member.size;
+            sent.get(range.clone()) != echoed.get(range)
+        })
+        .map(|member| Member {
+            name: rust_field_name(&member.name).to_owned(),
+            ..member.clone()
+        })
+}
+
+/// Returns the Rust field name of a WGSL member, e.g. `0` for `_0`.
+fn rust_field_name(wgsl: &str) -> &str {
+    match wgsl.strip_prefix('_') {
+        Some(index) if !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) => index,
+        _ => wgsl,
+    }
+}
+
+/// Formats sent and echoed bytes side by side, 16 bytes a row. Rows having different bytes start
+/// with `!`, and a line of `^` under them points the bytes.
+///
+/// ```text
+///         sent                                             echoed
+/// ! 0000  01 00 00 00 02 00 00 00                          01 00 00 00 00 00 00 00
+///                                                                      ^^
+/// ```
+pub fn hex_dump(sent: &[u8], echoed: &[u8]) -> String {
+    const ROW: usize = 16;
+    const COLUMN: usize = ROW * 3;
+
+    let mut dump = format!("        {:COLUMN$} echoed", "sent");
+    let rows = sent.len().max(echoed.len()).div_ceil(ROW);
+    for row in 0..rows {
+        let range = row * ROW..(row + 1) * ROW;
+        let sent = sent
+            .get(range.start..range.end.min(sent.len()))
+            .unwrap_or_default();
+        let echoed = echoed
+            .get(range.start..range.end.min(echoed.len()))
+            .unwrap_or_default();
+        let differs = (0..ROW)
+            .map(|i| sent.get(i) != echoed.get(i))
+            .collect::<Vec<_>>();
+
+        let marker = if differs.contains(&true) { '!' } else { ' ' };
+        write!(
+            dump,
+            "\n{marker} {:04x}  {:COLUMN$} ",
+            range.start,
+            hex(sent)
+        )
+        .unwrap();
+        write!(dump, "{}", hex(echoed).trim_end()).unwrap();
+
+        if marker == '!' {
+            let carets = differs
+                .iter()
+                .map(|&differs| if differs { "^^ " } else { "   " })
+                .collect::<String>();
+            write!(dump, "\n        {:COLUMN$} {}", "", carets.trim_end()).unwrap();
+        }
+    }
+    dump
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x} ")).collect()
+}
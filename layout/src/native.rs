@@ -0,0 +1,19 @@
+use crate::Gpu;
+use std::sync::OnceLock;
+
+/// Returns a [`Gpu`] shared by all tests in the test binary.
+///
+/// Backends are [`wgpu::Backends::PRIMARY`] by default. Set `WGPU_BACKEND` and `WGPU_ADAPTER_NAME`
+/// environment variables to choose others. e.g. `WGPU_ADAPTER_NAME=llvmpipe` for lavapipe.
+///
+/// Note that GL backend isn't suitable for layout tests. It translates WGSL into GLSL without
+/// `@size` and `@align` attributes, so data may be laid out differently from WebGPU.
+pub fn gpu() -> &'static Gpu {
+    static GPU: OnceLock<Gpu> = OnceLock::new();
+    GPU.get_or_init(|| pollster::block_on(Gpu::new(wgpu::Backends::PRIMARY.with_env())))
+}
+
+/// Runs a test case to completion on the current thread.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    pollster::block_on(future)
+}
@@ -0,0 +1,219 @@
+use crate::{
+    AddressSpace, Gpu, LayoutError, Member, TestResult,
+    address_space::{copy_array, echo_on_gpu, echo_shader_copying},
+    diff::differing_members,
+    validate::{init_buffer_type, layout_of, parse},
+};
+use core::{fmt, mem::ManuallyDrop, ptr, slice};
+use my_wgsl::WgslCompatible;
+
+/// Tests if the gpu can read the given slice correctly as a runtime-sized array, `array<T>`, in
+/// 'storage' address space.
+///
+/// Every element is compared, and the first differing one is reported as
+/// [`LayoutError::ElementMismatch`]. An empty slice is [`LayoutError::InvalidArgument`], since
+/// storage buffers can't be empty.
+///
+/// * gpu - A reference to a [`Gpu`].
+/// * data - The elements that will be sent to GPU.
+/// * dependencies - Type definitions `T` depends on.
+pub async fn test_runtime_array<T: WgslCompatible + fmt::Debug + PartialEq + 'static>(
+    gpu: &Gpu,
+    data: &[T],
+    dependencies: &[&str],
+) -> TestResult {
+    let define = dependencies
+        .iter()
+        .copied()
+        .chain([T::WGSL_DEFINE])
+        .collect::<Vec<_>>()
+        .join("\n");
+    test_runtime_array_with(gpu, data, Some(&define), T::WGSL_IDENT).await
+}
+
+/// Same as [`test_runtime_array`], but with a hand-written WGSL type.
+///
+/// * gpu - A reference to a [`Gpu`].
+/// * data - The elements that will be sent to GPU.
+/// * wgsl_define_type - Type definition in WGSL source code. e.g. "struct A { .. }"
+/// * wgsl_element_type - WGSL type of an element. e.g. "A" for `array<A>`
+pub async fn test_runtime_array_with<T: fmt::Debug + PartialEq + 'static>(
+    gpu: &Gpu,
+    data: &[T],
+    wgsl_define_type: Option<&str>,
+    wgsl_element_type: &str,
+) -> TestResult {
+    let wgsl_type = format!("array<{wgsl_element_type}>");
+    echo_runtime_array(gpu, &(), data, wgsl_define_type, &wgsl_type).await
+}
+
+/// Tests if the gpu can read a struct ending in a runtime-sized array correctly in 'storage'
+/// address space.
+///
+/// Rust doesn't let us build such structs easily, so the data is given in two parts, laid out as
+/// `#[repr(C)] struct { head: H, tail: [T] }`. e.g. `head` is `Head { count: u32 }` and `tail` is
+/// `&[Particle]` for `struct Particles { count: u32, items: array<Particle> }`.
+///
+/// * gpu - A reference to a [`Gpu`].
+/// * head - Members before the array.
+/// * tail - Elements of the array.
+/// * wgsl_define_type - Type definition in WGSL source code. e.g. "struct A { .. }"
+/// * wgsl_type - WGSL struct ending in a runtime-sized array.
+pub async fn test_runtime_array_struct_with<H, T>(
+    gpu: &Gpu,
+    head: &H,
+    tail: &[T],
+    wgsl_define_type: &str,
+    wgsl_type: &str,
+) -> TestResult
+where
+    H: fmt::Debug + PartialEq + 'static,
+    T: fmt::Debug + PartialEq + 'static,
+{
+    echo_runtime_array(gpu, head, tail, Some(wgsl_define_type), wgsl_type).await
+}
+
+async fn echo_runtime_array<H, T>(
+    gpu: &Gpu,
+    head: &H,
+    tail: &[T],
+    wgsl_define_type: Option<&str>,
+    wgsl_type: &str,
+) -> TestResult
+where
+    H: fmt::Debug + PartialEq + 'static,
+    T: fmt::Debug + PartialEq + 'static,
+{
+    if tail.is_empty() {
+        let message = format!("no elements of `{wgsl_type}` to test");
+        return Err(LayoutError::InvalidArgument(message));
+    }
+
+    // Lays out `head` and `tail` as Rust does.
+    let tail_offset = size_of::<H>().next_multiple_of(align_of::<T>());
+    let mut contents = vec![0; tail_offset + size_of_val(tail)];
+    contents[..size_of::<H>()].copy_from_slice(as_bytes(slice::from_ref(head)));
+    contents[tail_offset..].copy_from_slice(as_bytes(tail));
+
+    // Reads member names from the WGSL type to copy them one by one.
+    let declaration = echo_shader_copying(AddressSpace::Storage, wgsl_define_type, wgsl_type, "");
+    let module = parse(&declaration)?;
+    let mut layouter = naga::proc::Layouter::default();
+    layouter
+        .update(module.to_ctx())
+        .map_err(|e| LayoutError::Validation(e.to_string()))?;
+    let wgsl = WgslRuntimeArray::new(&module, &layouter, init_buffer_type(&module), wgsl_type)?;
+
+    let shader_code = echo_shader_copying(
+        AddressSpace::Storage,
+        wgsl_define_type,
+        wgsl_type,
+        &wgsl.copy,
+    );
+    let echoed = echo_on_gpu(gpu, AddressSpace::Storage, &contents, &shader_code).await?;
+
+    // Tests members before the array.
+    if size_of::<H>() > 0 {
+        let range = 0..size_of::<H>();
+        // Echoed bytes may not be aligned for `H`. And the copy must not be dropped, because it
+        // shares resources with `head` if there are any.
+        let read = ManuallyDrop::new(unsafe { ptr::read_unaligned(echoed.as_ptr() as *const H) });
+        if *read != *head {
+            return Err(LayoutError::ElementMismatch {
+                index: None,
+                field: differing_members(&wgsl.head, &contents[range.clone()], &echoed[range])
+                    .next()
+                    .map(|member| member.name),
+                sent: format!("{head:?}"),
+                echoed: format!("{:?}", *read),
+            });
+        }
+    }
+
+    // Tests elements of the array.
+    for (index, sent) in tail.iter().enumerate() {
+        let range =
+            tail_offset + index * size_of::<T>()..tail_offset + (index + 1) * size_of::<T>();
+        let read = ManuallyDrop::new(unsafe {
+            ptr::read_unaligned(echoed[range.start..].as_ptr() as *const T)
+        });
+        if *read != *sent {
+            return Err(LayoutError::ElementMismatch {
+                index: Some(index),
+                field: differing_members(&wgsl.element, &contents[range.clone()], &echoed[range])
+                    .next()
+                    .map(|member| member.name),
+                sent: format!("{sent:?}"),
+                echoed: format!("{:?}", *read),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// A runtime-sized array or a struct ending in one, read from WGSL.
+struct WgslRuntimeArray {
+    /// Members before the array.
+    head: Vec<Member>,
+    /// Members of an element. Empty if the element is not a struct.
+    element: Vec<Member>,
+    /// Statements copying `init_buffer` to `echo_buffer`.
+    copy: String,
+}
+
+impl WgslRuntimeArray {
+    fn new(
+        module: &naga::Module,
+        layouter: &naga::proc::Layouter,
+        ty: naga::Handle<naga::Type>,
+        wgsl_type: &str,
+    ) -> Result<Self, LayoutError> {
+        let runtime_array_base = |ty: naga::Handle<naga::Type>| match module.types[ty].inner {
+            naga::TypeInner::Array {
+                base,
+                size: naga::ArraySize::Dynamic,
+                ..
+            } => Some(base),
+            _ => None,
+        };
+        let element = |base| layout_of(module, layouter, base, AddressSpace::Storage).members;
+
+        // `array<T>`
+        if let Some(base) = runtime_array_base(ty) {
+            return Ok(Self {
+                head: Vec::new(),
+                element: element(base),
+                copy: copy_array(""),
+            });
+        }
+
+        // `struct S { .., tail: array<T> }`
+        let mut head = layout_of(module, layouter, ty, AddressSpace::Storage).members;
+        let naga::TypeInner::Struct { members, .. } = &module.types[ty].inner else {
+            let message = format!("`{wgsl_type}` is neither a runtime-sized array nor a struct");
+            return Err(LayoutError::InvalidArgument(message));
+        };
+        let Some(base) = members.last().and_then(|last| runtime_array_base(last.ty)) else {
+            let message = format!("`{wgsl_type}` doesn't end with a runtime-sized array");
+            return Err(LayoutError::InvalidArgument(message));
+        };
+        let tail = head.pop().unwrap();
+
+        let mut copy = head
+            .iter()
+            .map(|member| format!("echo_buffer.{0} = init_buffer.{0};\n", member.name))
+            .collect::<String>();
+        copy.push_str(&copy_array(&format!(".{}", tail.name)));
+
+        Ok(Self {
+            head,
+            element: element(base),
+            copy,
+        })
+    }
+}
+
+fn as_bytes<T>(data: &[T]) -> &[u8] {
+    unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, size_of_val(data)) }
+}
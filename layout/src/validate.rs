@@ -0,0 +1,254 @@
+use crate::{AddressSpace, LayoutError, TestResult, address_space::echo_shader};
+use core::fmt;
+use my_wgsl::WgslCompatible;
+
+/// Offset, size and alignment in bytes of a struct member.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub name: String,
+    pub offset: usize,
+    pub size: usize,
+    pub align: usize,
+}
+
+impl fmt::Display for Member {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "offset {}, size {}, align {}",
+            self.offset, self.size, self.align
+        )
+    }
+}
+
+/// Size and alignment in bytes of a type and its members. `members` is empty if the type is not a
+/// struct.
+///
+/// Use [`rust_layout!`](crate::rust_layout) for Rust types and [`wgsl_layout`] for WGSL types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub size: usize,
+    pub align: usize,
+    pub members: Vec<Member>,
+}
+
+/// A member laid out differently in WGSL and Rust.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberDiff {
+    pub wgsl: Member,
+    /// `None` if the Rust type doesn't have a field of the same name.
+    pub rust: Option<Member>,
+}
+
+impl fmt::Display for MemberDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`: WGSL ({}), ", self.wgsl.name, self.wgsl)?;
+        match &self.rust {
+            Some(rust) => write!(f, "Rust ({rust})"),
+            None => write!(f, "Rust (missing)"),
+        }
+    }
+}
+
+/// Makes a [`Layout`] of a `repr(C)` struct from the given fields.
+///
+/// Padding fields that don't appear in WGSL may be omitted.
+///
+/// ```
+/// #[repr(C)]
+/// struct B { b0: [i32; 4], b1: i32 }
+///
+/// let layout = layout::rust_layout!(B { b0, b1 });
+/// assert_eq!(layout.members[1].offset, 16);
+/// ```
+#[macro_export]
+macro_rules! rust_layout {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        $crate::Layout {
+            size: size_of::<$ty>(),
+            align: align_of::<$ty>(),
+            members: vec![$(
+                $crate::Member {
+                    name: stringify!($field).to_owned(),
+                    offset: core::mem::offset_of!($ty, $field),
+                    size: $crate::field_size(|t: &$ty| &t.$field),
+                    align: $crate::field_align(|t: &$ty| &t.$field),
+                }
+            ),*],
+        }
+    };
+}
+
+#[doc(hidden)]
+pub fn field_size<T, F>(_: fn(&T) -> &F) -> usize {
+    size_of::<F>()
+}
+
+#[doc(hidden)]
+pub fn field_align<T, F>(_: fn(&T) -> &F) -> usize {
+    align_of::<F>()
+}
+
+/// Tests if WGSL lays out `T` as Rust does in the given address space, without the GPU.
+///
+/// It parses and validates the same shader as [`test_address_space`](crate::test_address_space)
+/// with naga, so WGSL errors come out as [`LayoutError::Validation`]. Then it compares offsets
+/// and sizes of the members with `rust`, and returns [`LayoutError::Layout`] listing the members
+/// that differ.
+///
+/// * address_space - Where the gpu reads the data from.
+/// * rust - Layout of `T` in Rust. See [`rust_layout!`](crate::rust_layout).
+/// * dependencies - Type definitions `T` depends on.
+pub fn validate_layout<T: WgslCompatible>(
+    address_space: AddressSpace,
+    rust: &Layout,
+    dependencies: &[&str],
+) -> TestResult {
+    let define = dependencies
+        .iter()
+        .copied()
+        .chain([T::WGSL_DEFINE])
+        .collect::<Vec<_>>()
+        .join("\n");
+    validate_layout_with(address_space, rust, Some(&define), T::WGSL_IDENT)
+}
+
+/// Same as [`validate_layout`], but with a hand-written WGSL type.
+///
+/// * address_space - Where the gpu reads the data from.
+/// * rust - Layout of the type in Rust. See [`rust_layout!`](crate::rust_layout).
+/// * wgsl_define_type - Type definition in WGSL source code. e.g. "struct A { .. }"
+/// * wgsl_type - WGSL type corresponding to `rust`. If it's a runtime-sized array, `rust` is
+///   compared with an element of it, and its size with the array stride.
+pub fn validate_layout_with(
+    address_space: AddressSpace,
+    rust: &Layout,
+    wgsl_define_type: Option<&str>,
+    wgsl_type: &str,
+) -> TestResult {
+    let wgsl = wgsl_layout(address_space, wgsl_define_type, wgsl_type)?;
+
+    let members = wgsl
+        .members
+        .into_iter()
+        .filter_map(|wgsl| {
+            let rust = rust.members.iter().find(|rust| rust.name == wgsl.name);
+            match rust {
+                Some(rust) if (rust.offset, rust.size) == (wgsl.offset, wgsl.size) => None,
+                _ => Some(MemberDiff {
+                    wgsl,
+                    rust: rust.cloned(),
+                }),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if members.is_empty() && wgsl.size == rust.size {
+        Ok(())
+    } else {
+        Err(LayoutError::Layout {
+            wgsl_size: wgsl.size,
+            rust_size: rust.size,
+            members,
+        })
+    }
+}
+
+/// Computes the layout of a WGSL type in the given address space with naga.
+///
+/// Alignments are what the address space requires. e.g. 'uniform' address space requires structs
+/// and arrays to be aligned to 16 bytes. A runtime-sized array is laid out as one of its elements,
+/// but with the array stride as the size.
+///
+/// * address_space - Where the gpu reads the data from.
+/// * wgsl_define_type - Type definition in WGSL source code. e.g. "struct A { .. }"
+/// * wgsl_type - WGSL type to lay out.
+pub fn wgsl_layout(
+    address_space: AddressSpace,
+    wgsl_define_type: Option<&str>,
+    wgsl_type: &str,
+) -> Result<Layout, LayoutError> {
+    let shader_code = echo_shader(address_space, wgsl_define_type, wgsl_type);
+
+    // Parses and validates the shader as wgpu does before handing it to the GPU.
+    let module = parse(&shader_code)?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default() | naga::valid::Capabilities::SHADER_FLOAT16,
+    )
+    .validate(&module)
+    .map_err(|e| LayoutError::Validation(e.emit_to_string(&shader_code)))?;
+
+    let mut layouter = naga::proc::Layouter::default();
+    layouter.update(module.to_ctx()).unwrap();
+
+    // Runtime-sized arrays are laid out as their elements, but with the stride as the size.
+    let ty = init_buffer_type(&module);
+    Ok(match module.types[ty].inner {
+        naga::TypeInner::Array {
+            base,
+            size: naga::ArraySize::Dynamic,
+            stride,
+        } => Layout {
+            size: stride as usize,
+            ..layout_of(&module, &layouter, base, address_space)
+        },
+        _ => layout_of(&module, &layouter, ty, address_space),
+    })
+}
+
+/// Parses WGSL source code with naga.
+pub(crate) fn parse(shader_code: &str) -> Result<naga::Module, LayoutError> {
+    naga::front::wgsl::parse_str(shader_code)
+        .map_err(|e| LayoutError::Validation(e.emit_to_string(shader_code)))
+}
+
+/// Returns the type of `init_buffer` in an echo shader.
+pub(crate) fn init_buffer_type(module: &naga::Module) -> naga::Handle<naga::Type> {
+    let (_, init_buffer) = module
+        .global_variables
+        .iter()
+        .find(|(_, var)| var.name.as_deref() == Some("init_buffer"))
+        .unwrap();
+    init_buffer.ty
+}
+
+/// Lays out a type in the module. `layouter` must be up to date with the module.
+pub(crate) fn layout_of(
+    module: &naga::Module,
+    layouter: &naga::proc::Layouter,
+    ty: naga::Handle<naga::Type>,
+    address_space: AddressSpace,
+) -> Layout {
+    let align = |ty: naga::Handle<naga::Type>| {
+        // naga 27's `Alignment` has no getter. Rounding 1 up to the boundary gives the alignment
+        // itself.
+        let align = layouter[ty].alignment.round_up(1);
+        match (address_space, &module.types[ty].inner) {
+            (
+                AddressSpace::Uniform,
+                naga::TypeInner::Struct { .. } | naga::TypeInner::Array { .. },
+            ) => align.max(16) as usize,
+            _ => align as usize,
+        }
+    };
+
+    let members = match &module.types[ty].inner {
+        naga::TypeInner::Struct { members, .. } => members
+            .iter()
+            .map(|member| Member {
+                name: member.name.clone().unwrap_or_default(),
+                offset: member.offset as usize,
+                size: layouter[member.ty].size as usize,
+                align: align(member.ty),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Layout {
+        size: layouter[ty].size as usize,
+        align: align(ty),
+        members,
+    }
+}
@@ -0,0 +1,209 @@
+//! Runs [`layout::cases`] natively. Run `cargo test-native` in this directory.
+//!
+//! Tests ending with `_on_cpu` need no GPU.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use layout::{
+    LayoutError, TestResult, cases,
+    native::{block_on, gpu},
+};
+
+/// Asserts that validation failed with a message containing `pattern`.
+#[track_caller]
+fn assert_validation(result: TestResult, pattern: &str) {
+    match result {
+        Err(LayoutError::Validation(message)) if message.contains(pattern) => {}
+        _ => panic!("expected validation error containing {pattern:?}, but got {result:?}"),
+    }
+}
+
+/// Asserts that exactly the given members are laid out differently.
+#[track_caller]
+fn assert_layout(result: TestResult, expected: &[&str]) {
+    let Err(LayoutError::Layout { members, .. }) = &result else {
+        panic!("expected layout mismatch, but got {result:?}");
+    };
+    let members = members
+        .iter()
+        .map(|member| member.wgsl.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(members, expected);
+}
+
+#[test]
+fn uniform_offset() {
+    let result = block_on(cases::uniform_offset::fail(gpu()));
+    assert!(
+        matches!(result, Err(LayoutError::Validation(_))),
+        "{result:?}"
+    );
+
+    let result = block_on(cases::uniform_offset::ok(gpu()));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn uniform_offset_on_cpu() {
+    let result = cases::uniform_offset::fail_on_cpu();
+    assert!(
+        matches!(result, Err(LayoutError::Validation(_))),
+        "{result:?}"
+    );
+
+    let result = cases::uniform_offset::ok_on_cpu();
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn vec3_align() {
+    let result = block_on(cases::vec3_align::fail(gpu()));
+    assert_validation(result, "minimum 32");
+
+    let result = block_on(cases::vec3_align::ok(gpu()));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn vec3_align_on_cpu() {
+    assert_layout(cases::vec3_align::fail_on_cpu(), &["a1"]);
+    assert_eq!(cases::vec3_align::ok_on_cpu(), Ok(()));
+}
+
+#[test]
+fn uniform_array_stride() {
+    let result = block_on(cases::uniform_array_stride::fail(gpu()));
+    assert_validation(result, "array stride 4");
+
+    let result = block_on(cases::uniform_array_stride::ok(gpu()));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn uniform_array_stride_on_cpu() {
+    assert_validation(cases::uniform_array_stride::fail_on_cpu(), "array stride 4");
+    assert_eq!(cases::uniform_array_stride::ok_on_cpu(), Ok(()));
+}
+
+#[test]
+fn struct_size() {
+    let result = block_on(cases::struct_size::fail(gpu()));
+    assert_validation(result, "minimum 24");
+
+    let result = block_on(cases::struct_size::ok(gpu()));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn struct_size_on_cpu() {
+    assert_layout(cases::struct_size::fail_on_cpu(), &["b0", "b1"]);
+    assert_eq!(cases::struct_size::ok_on_cpu(), Ok(()));
+}
+
+#[test]
+fn runtime_array() {
+    let result = block_on(cases::runtime_array::fail(gpu()));
+    assert_validation(result, "SIZED");
+
+    let result = block_on(cases::runtime_array::ok(gpu()));
+    assert_eq!(result, Ok(()));
+
+    // The first element is read from the wrong offset, and `pos` comes first.
+    let result = block_on(cases::runtime_array::struct_fail(gpu()));
+    assert!(
+        matches!(
+            &result,
+            Err(LayoutError::ElementMismatch { index: Some(0), field: Some(field), .. })
+                if field == "pos"
+        ),
+        "{result:?}"
+    );
+
+    let result = block_on(cases::runtime_array::struct_ok(gpu()));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn runtime_array_invalid_argument() {
+    let result = block_on(layout::test_runtime_array_with::<f32>(
+        gpu(),
+        &[],
+        None,
+        "f32",
+    ));
+    assert!(
+        matches!(result, Err(LayoutError::InvalidArgument(_))),
+        "{result:?}"
+    );
+
+    let define = "struct Head { count: u32 }";
+    let result = block_on(layout::test_runtime_array_struct_with(
+        gpu(),
+        &0_u32,
+        &[1.0_f32],
+        define,
+        "Head",
+    ));
+    assert!(
+        matches!(result, Err(LayoutError::InvalidArgument(_))),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn runtime_array_on_cpu() {
+    assert_validation(cases::runtime_array::fail_on_cpu(), "SIZED");
+    assert_eq!(cases::runtime_array::ok_on_cpu(), Ok(()));
+}
+
+#[test]
+fn align_size_attributes() {
+    let result = block_on(cases::align_size_attributes::fail(gpu()));
+    assert!(
+        matches!(result, Err(LayoutError::Mismatch { .. })),
+        "{result:?}"
+    );
+
+    let result = block_on(cases::align_size_attributes::ok(gpu()));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn align_size_attributes_on_cpu() {
+    assert_layout(cases::align_size_attributes::fail_on_cpu(), &["a1", "a2"]);
+    assert_eq!(cases::align_size_attributes::ok_on_cpu(), Ok(()));
+}
+
+#[test]
+fn mat3x3() {
+    let result = block_on(cases::mat3x3::fail(gpu()));
+    assert_validation(result, "minimum 48");
+
+    let result = block_on(cases::mat3x3::ok(gpu()));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn mat3x3_on_cpu() {
+    assert_layout(cases::mat3x3::fail_on_cpu(), &["a0"]);
+    assert_eq!(cases::mat3x3::ok_on_cpu(), Ok(()));
+}
+
+#[test]
+fn f16() {
+    let result = block_on(cases::f16::fail(gpu()));
+    assert_validation(result, "`f16` enable extension");
+
+    if !gpu().device.features().contains(wgpu::Features::SHADER_F16) {
+        eprintln!("skipped f16::ok. The adapter doesn't support f16.");
+        return;
+    }
+    let result = block_on(cases::f16::ok(gpu()));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn f16_on_cpu() {
+    assert_validation(cases::f16::fail_on_cpu(), "`f16` enable extension");
+    assert_eq!(cases::f16::ok_on_cpu(), Ok(()));
+}
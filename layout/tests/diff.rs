@@ -0,0 +1,32 @@
+//! Tests how [`layout::LayoutError`] shows differences. Run `cargo test-native` in this directory.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use layout::hex_dump;
+
+#[test]
+fn hex_dump_marks_differing_bytes() {
+    let sent = (0..20).collect::<Vec<u8>>();
+    let mut echoed = sent.clone();
+    echoed[17] = 0xff;
+
+    let expected = [
+        "        sent                                             echoed",
+        "  0000  00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f  00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f",
+        "! 0010  10 11 12 13                                      10 ff 12 13",
+        "                                                            ^^",
+    ];
+    assert_eq!(hex_dump(&sent, &echoed), expected.join("\n"));
+}
+
+#[test]
+fn hex_dump_marks_missing_bytes() {
+    let dump = hex_dump(&[1, 2, 3, 4], &[1, 2]);
+    assert_eq!(
+        dump.lines().skip(1).collect::<Vec<_>>(),
+        [
+            "! 0000  01 02 03 04                                      01 02",
+            "                                                               ^^ ^^",
+        ]
+    );
+}
@@ -1,5 +1,5 @@
 use std::{ops::Deref, ptr::NonNull};
-use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen::{JsCast, JsError, JsValue};
 
 #[derive(Debug, Clone)]
 pub struct Canvas {
@@ -8,9 +8,11 @@ pub struct Canvas {
 }
 
 impl Canvas {
-    pub fn new(selectors: &str, handle: u32) -> Self {
-        // 0 is reserved for window itself.
-        assert!(handle > 0);
+    /// Returns an error if `handle` is 0, which is reserved for window itself.
+    pub fn new(selectors: &str, handle: u32) -> Result<Self, JsError> {
+        if handle == 0 {
+            return Err(JsError::new("canvas handle 0 is reserved for window"));
+        }
 
         // Injects `data-raw-handle` attribute into the canvas element.
         // This is required by `wgpu::Surface` and `raw-window-handle`.
@@ -19,7 +21,7 @@ impl Canvas {
             .set_attribute("data-raw-handle", handle.to_string().as_str())
             .unwrap();
 
-        Self { element, handle }
+        Ok(Self { element, handle })
     }
 
     pub fn get_canvas_element(selectors: &str) -> web_sys::HtmlCanvasElement {
@@ -39,6 +41,13 @@ impl Canvas {
     pub fn handle(&self) -> u32 {
         self.handle
     }
+
+    /// Replaces the element in the document with a copy of it.
+    /// A canvas can be transferred to offscreen only once, but the copy can be transferred again.
+    pub fn reset(&self) {
+        let fresh = self.element.clone_node().unwrap();
+        self.element.replace_with_with_node_1(&fresh).unwrap();
+    }
 }
 
 impl Deref for Canvas {
@@ -91,6 +100,11 @@ impl OffscreenCanvas {
     pub fn each(self) -> (web_sys::OffscreenCanvas, u32) {
         (self.inner, self.handle)
     }
+
+    #[inline]
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
 }
 
 impl Deref for OffscreenCanvas {
@@ -0,0 +1,117 @@
+//! Tasks run by compute workers of [`WorkerPool`](super::worker::WorkerPool).
+
+use super::message::*;
+use renderer::Vertex;
+use wasm_bindgen::prelude::*;
+
+/// Number of f32 in a vertex of [`JsOutput::Mesh`].
+const VERTEX_LEN: usize = 6;
+/// Upper limit of quads in a grid, which keeps buffers in a few tens of MB.
+const MAX_QUADS: u32 = 1 << 20;
+
+/// Compute worker's message handler running jobs.
+/// JS calls this once wasm is ready, so there's nothing to initialize.
+#[wasm_bindgen]
+pub fn compute_onmessage(event: web_sys::MessageEvent) {
+    let job = match JsJob::from_js(event.data()) {
+        Ok(job) => job,
+        Err(e) => {
+            crate::log!("{e}");
+            return;
+        }
+    };
+
+    let output = run(job.task);
+    let transfer = match &output {
+        Ok(output) => output.transfer(),
+        Err(_) => js_sys::Array::new(),
+    };
+    let result = JsJobResult { id: job.id, output };
+    let global = js_sys::global().unchecked_into::<web_sys::DedicatedWorkerGlobalScope>();
+    global
+        .post_message_with_transfer(&result.to_js(), &transfer)
+        .unwrap();
+}
+
+fn run(task: JsTask) -> Result<JsOutput, String> {
+    match task {
+        JsTask::GridMesh { columns, rows } => grid_mesh(columns, rows),
+    }
+}
+
+/// Generates a grid over clip space, colored by position.
+fn grid_mesh(columns: u32, rows: u32) -> Result<JsOutput, String> {
+    if columns == 0 || rows == 0 || columns.saturating_mul(rows) > MAX_QUADS {
+        return Err(format!(
+            "grid of {columns} x {rows} quads is empty or over {MAX_QUADS}"
+        ));
+    }
+
+    let mut vertices = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize * VERTEX_LEN);
+    for j in 0..=rows {
+        for i in 0..=columns {
+            let u = i as f32 / columns as f32;
+            let v = j as f32 / rows as f32;
+            vertices.extend([u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, u, v, 1.0 - u]);
+        }
+    }
+
+    // CCW, same as `QUAD_INDICES`.
+    let mut indices = Vec::with_capacity((columns * rows * 6) as usize);
+    for j in 0..rows {
+        for i in 0..columns {
+            let top_left = j * (columns + 1) + i;
+            let top_right = top_left + 1;
+            let bottom_left = top_left + columns + 1;
+            let bottom_right = bottom_left + 1;
+            indices.extend([
+                top_left,
+                bottom_left,
+                top_right,
+                top_right,
+                bottom_left,
+                bottom_right,
+            ]);
+        }
+    }
+
+    Ok(JsOutput::Mesh {
+        vertices: js_sys::Float32Array::from(&vertices[..]).buffer(),
+        indices: js_sys::Uint32Array::from(&indices[..]).buffer(),
+    })
+}
+
+/// Reads buffers of [`JsOutput::Mesh`]'s layout, checking that they make a mesh.
+pub fn read_mesh(
+    vertices: &js_sys::ArrayBuffer,
+    indices: &js_sys::ArrayBuffer,
+) -> Result<(Vec<Vertex>, Vec<u32>), String> {
+    // Typed arrays can't view buffers of other lengths.
+    if !vertices.byte_length().is_multiple_of(4) || !indices.byte_length().is_multiple_of(4) {
+        return Err("buffers aren't of f32 or u32".to_owned());
+    }
+    let floats = js_sys::Float32Array::new(vertices).to_vec();
+    let indices = js_sys::Uint32Array::new(indices).to_vec();
+    if !floats.len().is_multiple_of(VERTEX_LEN) || !indices.len().is_multiple_of(3) {
+        return Err(format!(
+            "{} floats or {} indices don't make a mesh",
+            floats.len(),
+            indices.len()
+        ));
+    }
+
+    let vertices = floats
+        .chunks_exact(VERTEX_LEN)
+        .map(|v| Vertex {
+            pos: [v[0], v[1], v[2]],
+            color: [v[3], v[4], v[5]],
+        })
+        .collect::<Vec<_>>();
+    if let Some(index) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+        return Err(format!(
+            "index {index} is out of {} vertices",
+            vertices.len()
+        ));
+    }
+    Ok((vertices, indices))
+}
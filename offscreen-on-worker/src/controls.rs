@@ -0,0 +1,164 @@
+use super::message::*;
+use renderer::Input;
+use std::collections::HashMap;
+
+/// Radians per physical pixel of dragging.
+const ORBIT_SPEED: f32 = 0.005;
+/// Radians per frame an arrow key is held.
+const ORBIT_STEP: f32 = 0.02;
+/// Physical pixels per frame an arrow key is held with shift.
+const PAN_STEP: f32 = 4.0;
+/// Zoom factor exponent per wheel pixel.
+const ZOOM_SPEED: f32 = 0.001;
+/// Zoom factor per frame a zoom key is held.
+const ZOOM_STEP: f32 = 1.02;
+
+const MIN_DISTANCE: f32 = 0.1;
+const MAX_DISTANCE: f32 = 100.0;
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+/// Depth scale of the view, so that rotated vertices within a unit sphere aren't clipped.
+const DEPTH_SCALE: f32 = 0.25;
+
+/// Orbit, zoom and pan camera controls driven by forwarded window input.
+///
+/// Dragging with the primary button or one finger orbits, dragging with the other buttons or
+/// shift pans, and the wheel or pinching zooms. Arrow keys orbit, or pan with shift, and `+`/`-`
+/// zoom. Positions are in physical pixels. [`Controls::view`] turns them into the view transform
+/// the shader applies.
+///
+/// Pointer drags, the wheel and keys are read from [`Input`] once a frame. Touches are tracked
+/// here, since pinching needs more than one pointer.
+#[derive(Debug)]
+pub struct Controls {
+    /// Rotation around the vertical axis in radians.
+    pub yaw: f32,
+    /// Rotation around the horizontal axis in radians.
+    pub pitch: f32,
+    /// Distance from the target.
+    pub distance: f32,
+    /// Target offset in physical pixels.
+    pub pan: [f32; 2],
+    /// Touch positions by identifier.
+    touches: HashMap<i32, [f32; 2]>,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: 0.0,
+            distance: 1.0,
+            pan: [0.0, 0.0],
+            touches: HashMap::new(),
+        }
+    }
+}
+
+impl Controls {
+    /// Returns the view transform as columns, which orbits around the origin, then scales by
+    /// `distance` and moves by `pan`.
+    ///
+    /// * resolution - Surface size in physical pixels, which `pan` is converted with.
+    pub fn view(&self, resolution: [f32; 2]) -> [[f32; 4]; 4] {
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        let k = 1.0 / self.distance;
+        let d = DEPTH_SCALE;
+        // Clip space y goes up while pixels go down.
+        let tx = self.pan[0] * 2.0 / resolution[0].max(1.0);
+        let ty = -self.pan[1] * 2.0 / resolution[1].max(1.0);
+        [
+            [k * cy, k * sp * sy, -d * cp * sy, 0.0],
+            [0.0, k * cp, d * sp, 0.0],
+            [k * sy, -k * sp * cy, d * cp * cy, 0.0],
+            [tx, ty, 0.5, 1.0],
+        ]
+    }
+
+    /// Moves the view by what's accumulated in the input in this frame, and by held keys.
+    /// Call this before [`Input::end_frame`].
+    pub fn update(&mut self, input: &Input) {
+        let shift = input.is_pressed("ShiftLeft") || input.is_pressed("ShiftRight");
+
+        let [dx, dy] = input.drag;
+        if input.buttons & 1 != 0 && !shift {
+            self.orbit(dx * ORBIT_SPEED, dy * ORBIT_SPEED);
+        } else {
+            self.pan[0] += dx;
+            self.pan[1] += dy;
+        }
+
+        self.zoom((input.wheel[1] * ZOOM_SPEED).exp());
+
+        let held = |code: &str| if input.is_pressed(code) { 1.0 } else { 0.0 };
+        let x = held("ArrowRight") - held("ArrowLeft");
+        let y = held("ArrowDown") - held("ArrowUp");
+        if shift {
+            self.pan[0] += x * PAN_STEP;
+            self.pan[1] += y * PAN_STEP;
+        } else {
+            self.orbit(x * ORBIT_STEP, y * ORBIT_STEP);
+        }
+        let zoom_in = held("Equal").max(held("NumpadAdd"));
+        let zoom_out = held("Minus").max(held("NumpadSubtract"));
+        self.zoom(ZOOM_STEP.powf(zoom_out - zoom_in));
+    }
+
+    pub fn touch_start(&mut self, msg: &JsTouchMessage) {
+        self.set_touches(msg);
+    }
+
+    pub fn touch_move(&mut self, msg: &JsTouchMessage) {
+        let scale = msg.scale_factor as f32;
+        let moved = |touch: &JsTouch| {
+            let new = [touch.offset_x as f32 * scale, touch.offset_y as f32 * scale];
+            self.touches.get(&touch.identifier).map(|old| (*old, new))
+        };
+        match msg.touches.iter().filter_map(moved).collect::<Vec<_>>()[..] {
+            // One finger orbits.
+            [(old, new)] => {
+                self.orbit(
+                    (new[0] - old[0]) * ORBIT_SPEED,
+                    (new[1] - old[1]) * ORBIT_SPEED,
+                );
+            }
+            // Two fingers pinch to zoom, and pan with their center.
+            [(old0, new0), (old1, new1), ..] => {
+                let length = |a: [f32; 2], b: [f32; 2]| (a[0] - b[0]).hypot(a[1] - b[1]);
+                let (old_length, new_length) = (length(old0, old1), length(new0, new1));
+                if old_length > 0.0 && new_length > 0.0 {
+                    self.zoom(old_length / new_length);
+                }
+                self.pan[0] += (new0[0] + new1[0] - old0[0] - old1[0]) / 2.0;
+                self.pan[1] += (new0[1] + new1[1] - old0[1] - old1[1]) / 2.0;
+            }
+            _ => {}
+        }
+        self.set_touches(msg);
+    }
+
+    pub fn touch_end(&mut self, msg: &JsTouchMessage) {
+        self.set_touches(msg);
+    }
+
+    fn set_touches(&mut self, msg: &JsTouchMessage) {
+        let scale = msg.scale_factor as f32;
+        self.touches = msg
+            .touches
+            .iter()
+            .map(|touch| {
+                let pos = [touch.offset_x as f32 * scale, touch.offset_y as f32 * scale];
+                (touch.identifier, pos)
+            })
+            .collect();
+    }
+
+    fn orbit(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw).rem_euclid(std::f32::consts::TAU);
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    fn zoom(&mut self, factor: f32) {
+        self.distance = (self.distance * factor).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+}
@@ -2,7 +2,9 @@ struct UniformData {
     mouse_move: vec2<f32>,
     mouse_click: vec2<f32>,
     resolution: vec2<f32>,
+    scale: f32,
     time: f32,
+    view: mat4x4<f32>,
 }
 
 @group(0) @binding(0) var<uniform> uni: UniformData;
@@ -20,7 +22,7 @@ struct VertexOutput {
 @vertex
 fn v_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    out.pos = vec4<f32>(in.pos, 1.0);
+    out.pos = uni.view * vec4<f32>(in.pos, 1.0);
     let fluc = sin(modf(uni.time).fract * 3.141592) * 0.3 + 0.7;
     out.color = in.color * fluc;
     return out;
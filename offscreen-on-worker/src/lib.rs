@@ -1,437 +1,1023 @@
-use std::{cell::RefCell, mem, rc::Rc};
+use renderer::{
+    wgpu, Context, DeviceEvent, Error, Input, Mesh, Renderer, RendererDescriptor, QUAD_INDICES,
+    QUAD_VERTICES,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fmt,
+    rc::Rc,
+};
 use wasm_bindgen::prelude::*;
-use wgpu::util::DeviceExt;
 mod worker;
 use worker::*;
 mod canvas;
 use canvas::*;
 mod message;
 use message::*;
+mod controls;
+use controls::*;
+mod stats;
+use stats::*;
+mod ring;
+use ring::*;
+mod compute;
+use compute::*;
 
 /// `App` is responsible for accessing window elements.
 /// Also, it creates main worker and passes window events to the worker.
 /// Main worker, on the other hand, does all works such as drawing.
-#[allow(dead_code)]
+/// CPU heavy jobs such as mesh generation run on a pool of compute workers.
 #[wasm_bindgen]
 pub struct App {
-    canvas: Canvas,
-    worker: Rc<RefCell<MainWorker>>,
+    canvases: Rc<RefCell<Vec<Canvas>>>,
+    sender: Rc<Sender>,
+    listeners: Rc<RefCell<ReplyListeners>>,
+    /// Event listeners on the window and the canvases, removed when `App` is dropped.
+    proxies: Vec<Proxy>,
+    pool: WorkerPool,
 }
 
 #[wasm_bindgen]
 impl App {
     /// `index.js` creates `App` using this constructor.
+    /// Then, it adds canvases to draw onto with [`App::add_canvas`].
     #[allow(clippy::new_without_default)]
     #[wasm_bindgen(constructor)]
     pub fn new() -> Self {
+        // Spawns main worker, which replies through `listeners`.
+        let listeners = Rc::new(RefCell::new(ReplyListeners::default()));
+        let listeners_cloned = Rc::clone(&listeners);
+        let onmessage = move |event: web_sys::MessageEvent| match JsReply::from_js(event.data()) {
+            Ok(reply) => listeners_cloned.borrow().notify(&reply),
+            Err(e) => {
+                crate::log!("{e}");
+            }
+        };
+        let worker = Worker::spawn(WorkerKind::Main, "main-worker", 1, onmessage).unwrap();
+        worker.post_message(&JsMessage::Init.to_js()).unwrap();
+
+        // Shares input ring with the worker if possible.
+        let ring = InputRing::is_available().then(|| {
+            let ring = InputRing::new(RING_CAPACITY);
+            let buffer = ring.buffer().clone();
+            let msg = JsMessage::AttachRing { buffer };
+            worker.post_message(&msg.to_js()).unwrap();
+            ring
+        });
+        let sender = Rc::new(Sender { worker, ring });
+        let canvases = Rc::new(RefCell::new(Vec::<Canvas>::new()));
+
+        // Registers "resize" event proxy for all canvases.
+        let sender_cloned = Rc::clone(&sender);
+        let canvases_cloned = Rc::clone(&canvases);
+        let listener = Closure::<dyn Fn(_)>::new(move |_: web_sys::Event| {
+            let window = web_sys::window().unwrap();
+            let scale_factor = window.device_pixel_ratio();
+            for canvas in canvases_cloned.borrow().iter() {
+                let msg = JsMessage::WindowResize(JsResizeMessage::new(canvas, scale_factor));
+                sender_cloned.send(&msg);
+            }
+        });
+        let window = web_sys::window().unwrap();
+        let mut proxies = vec![Proxy::new(&window, "resize", listener)];
+
+        // Registers keyboard event proxies.
+        // Canvas doesn't get focus by default, so listens to the window.
+        proxies.push(add_proxy(&window, "keydown", &sender, |event, _| {
+            JsMessage::KeyDown(JsKeyMessage::new(&event))
+        }));
+        proxies.push(add_proxy(&window, "keyup", &sender, |event, _| {
+            JsMessage::KeyUp(JsKeyMessage::new(&event))
+        }));
+
+        let pool = WorkerPool::new(WorkerPool::default_size()).unwrap();
+
+        Self {
+            canvases,
+            sender,
+            listeners,
+            proxies,
+            pool,
+        }
+    }
+
+    /// Makes the worker release its device and surfaces and create them again.
+    /// Replies `{ type: "initialized" }` when it's done.
+    /// After `{ type: "initError" }`, this tries to initialize the worker again.
+    pub fn reinit(&self) {
+        let msg = JsMessage::Reinit.to_js();
+        self.sender.worker.post_message(&msg).unwrap();
+    }
+
+    /// Replaces the shader of all canvases with WGSL source, for hot reloading in dev mode.
+    /// Replies `{ type: "shaderReloaded" }`, or `{ type: "shaderError", message }` keeping the last
+    /// good shader.
+    pub fn set_shader(&self, source: String) {
+        let msg = JsMessage::SetShader { source }.to_js();
+        self.sender.worker.post_message(&msg).unwrap();
+    }
+
+    /// Sets a JS function receiving replies from the worker such as `{ type: "initialized" }`.
+    /// `null` removes it.
+    pub fn set_onreply(&self, callback: Option<js_sys::Function>) {
+        self.listeners.borrow_mut().js = callback;
+    }
+
+    /// Generates a grid mesh of `columns` x `rows` quads on a compute worker, then hands it to the
+    /// main worker to draw onto the canvas of `handle`.
+    /// Returns a promise resolved once it's handed, or rejected with the reason it failed.
+    pub fn generate_mesh(&self, handle: u32, columns: u32, rows: u32) -> js_sys::Promise {
+        let job = self.pool.submit(JsTask::GridMesh { columns, rows });
+        let sender = Rc::clone(&self.sender);
+        wasm_bindgen_futures::future_to_promise(async move {
+            let output = job.await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+            let JsOutput::Mesh { vertices, indices } = output;
+
+            // Buffers move on to main worker without copying.
+            let t = js_sys::Array::of2(&vertices, &indices);
+            let msg = JsMessage::SetMesh {
+                handle,
+                vertices,
+                indices,
+            };
+            sender.worker.post_message_with_transfer(&msg.to_js(), &t)?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Transfers the canvas found by `selectors` to the worker, which draws onto it.
+    /// Messages from the canvas are routed by `handle`, which must be unique and greater than 0.
+    /// Throws if it's not, and the canvas isn't transferred.
+    pub fn add_canvas(&mut self, selectors: &str, handle: u32) -> Result<(), JsError> {
+        if self.canvases.borrow().iter().any(|c| c.handle() == handle) {
+            let message = format!("canvas handle {handle} is already in use");
+            return Err(JsError::new(&message));
+        }
+
         // Gets the canvas.
-        let canvas = Canvas::new("#canvas0", 1);
-        // Spawns main worker.
-        let worker = MainWorker::spawn("main-worker", 1).unwrap();
+        let canvas = Canvas::new(selectors, handle)?;
 
         // Transfers offscreen canvas to the worker.
         let offscreen = OffscreenCanvas::from(&canvas);
         let (offscreen, handle) = offscreen.each();
-        let msg = js_sys::Array::new_with_length(3);
-        msg.set(0, JsMessage::INIT.into_jsvalue());
-        msg.set(1, JsValue::from(offscreen.clone()));
-        msg.set(2, JsValue::from(handle));
+        let msg = JsMessage::AddCanvas {
+            canvas: offscreen.clone(),
+            handle,
+        };
         let t = js_sys::Array::new_with_length(1);
         t.set(0, JsValue::from(offscreen));
-        worker.post_message_with_transfer(&msg, &t).unwrap();
-        let worker = Rc::new(RefCell::new(worker));
-
-        // Registers "resize" event proxy.
-        let worker_cloned = Rc::clone(&worker);
-        let msg = js_sys::Array::new_with_length(JsResizeMessage::field_num() + 1);
-        msg.set(0, JsMessage::WINDOW_RESIZE.into_jsvalue());
-        let canvas_cloned = canvas.clone();
-        let listener = Closure::<dyn Fn()>::new(move || {
-            let window = web_sys::window().unwrap();
-            let scale_factor = window.device_pixel_ratio();
-            JsResizeMessage::set_js_array(&msg, &canvas_cloned, scale_factor, 1);
-            worker_cloned.borrow_mut().post_message(&msg).unwrap();
-        });
-        let window = web_sys::window().unwrap();
-        window
-            .add_event_listener_with_callback("resize", listener.as_ref().unchecked_ref())
+        self.sender
+            .worker
+            .post_message_with_transfer(&msg.to_js(), &t)
             .unwrap();
-        listener.forget(); // Leak, but just once.
 
-        // Registers "mousemove" event proxy.
-        let worker_cloned = Rc::clone(&worker);
-        let msg = js_sys::Array::new_with_length(JsMouseMessage::field_num() + 1);
-        let listener = Closure::<dyn Fn(_)>::new(move |event: web_sys::MouseEvent| {
-            let window = web_sys::window().unwrap();
-            let scale_factor = window.device_pixel_ratio();
-            msg.set(0, JsMessage::MOUSE_MOVE.into_jsvalue());
-            JsMouseMessage::set_js_array(&msg, event, scale_factor, 1);
-            worker_cloned.borrow_mut().post_message(&msg).unwrap();
-        });
-        canvas
-            .add_event_listener_with_callback("mousemove", listener.as_ref().unchecked_ref())
-            .unwrap();
-        listener.forget(); // Leak, but just once.
+        let sender = &self.sender;
+        let proxies = &mut self.proxies;
 
-        // Registers "click" event proxy.
-        let worker_cloned = Rc::clone(&worker);
-        let msg = js_sys::Array::new_with_length(JsMouseMessage::field_num() + 1);
-        let listener = Closure::<dyn Fn(_)>::new(move |event: web_sys::MouseEvent| {
-            let window = web_sys::window().unwrap();
-            let scale_factor = window.device_pixel_ratio();
-            msg.set(0, JsMessage::MOUSE_CLICK.into_jsvalue());
-            JsMouseMessage::set_js_array(&msg, event, scale_factor, 1);
-            worker_cloned.borrow_mut().post_message(&msg).unwrap();
-        });
-        canvas
-            .add_event_listener_with_callback("click", listener.as_ref().unchecked_ref())
+        // Registers mouse event proxies.
+        proxies.push(add_proxy(
+            &canvas,
+            "mousemove",
+            sender,
+            move |event, scale_factor| {
+                JsMessage::MouseMove(JsMouseMessage::new(&event, handle, scale_factor))
+            },
+        ));
+        proxies.push(add_proxy(
+            &canvas,
+            "click",
+            sender,
+            move |event, scale_factor| {
+                JsMessage::MouseClick(JsMouseMessage::new(&event, handle, scale_factor))
+            },
+        ));
+
+        // Registers wheel event proxy, which zooms instead of scrolling the page.
+        proxies.push(add_proxy(
+            &canvas,
+            "wheel",
+            sender,
+            move |event: web_sys::WheelEvent, scale_factor| {
+                event.prevent_default();
+                JsMessage::Wheel(JsWheelMessage::new(&event, handle, scale_factor))
+            },
+        ));
+
+        // Registers pointer event proxies.
+        proxies.push(add_proxy(
+            &canvas,
+            "pointerdown",
+            sender,
+            move |event, scale_factor| {
+                JsMessage::PointerDown(JsPointerMessage::new(&event, handle, scale_factor))
+            },
+        ));
+        proxies.push(add_proxy(
+            &canvas,
+            "pointerup",
+            sender,
+            move |event, scale_factor| {
+                JsMessage::PointerUp(JsPointerMessage::new(&event, handle, scale_factor))
+            },
+        ));
+        proxies.push(add_proxy(
+            &canvas,
+            "pointercancel",
+            sender,
+            move |event, scale_factor| {
+                JsMessage::PointerCancel(JsPointerMessage::new(&event, handle, scale_factor))
+            },
+        ));
+
+        // Registers touch event proxies, which take touches from page scrolling and zooming.
+        type TouchFn = fn(JsTouchMessage) -> JsMessage;
+        let touches: [(&str, TouchFn); 4] = [
+            ("touchstart", JsMessage::TouchStart),
+            ("touchmove", JsMessage::TouchMove),
+            ("touchend", JsMessage::TouchEnd),
+            ("touchcancel", JsMessage::TouchCancel),
+        ];
+        for (event_type, to_message) in touches {
+            let canvas_cloned = canvas.clone();
+            proxies.push(add_proxy(
+                &canvas,
+                event_type,
+                sender,
+                move |event: web_sys::TouchEvent, scale_factor| {
+                    event.prevent_default();
+                    to_message(JsTouchMessage::new(&event, &canvas_cloned, scale_factor))
+                },
+            ));
+        }
+
+        self.canvases.borrow_mut().push(canvas);
+        Ok(())
+    }
+}
+
+impl Drop for App {
+    /// Lets the worker shut down and the canvases be used by a new `App`.
+    ///
+    /// Removing `proxies` drops the last [`Sender`], whose worker is then asked to shut down.
+    fn drop(&mut self) {
+        // Canvases were transferred to the worker, so replaces them with fresh ones.
+        for canvas in self.canvases.borrow().iter() {
+            canvas.reset();
+        }
+    }
+}
+
+impl App {
+    /// Adds a listener receiving replies from the worker.
+    pub fn add_reply_listener(&self, listener: impl Fn(&JsReply) + 'static) {
+        self.listeners.borrow_mut().rust.push(Box::new(listener));
+    }
+
+    /// Pool of compute workers, which runs other jobs as well.
+    pub fn pool(&self) -> &WorkerPool {
+        &self.pool
+    }
+}
+
+type ReplyListener = Box<dyn Fn(&JsReply)>;
+
+/// Receivers of [`JsReply`] in window context.
+#[derive(Default)]
+struct ReplyListeners {
+    rust: Vec<ReplyListener>,
+    js: Option<js_sys::Function>,
+}
+
+impl ReplyListeners {
+    fn notify(&self, reply: &JsReply) {
+        for listener in &self.rust {
+            listener(reply);
+        }
+        if let Some(js) = &self.js {
+            // Infallible, all fields are representable in JS.
+            let value = serde_wasm_bindgen::to_value(reply).unwrap();
+            if let Err(e) = js.call1(&JsValue::NULL, &value) {
+                crate::log!("onreply threw {e:?}");
+            }
+        }
+    }
+}
+
+/// Number of records [`InputRing`] can hold.
+const RING_CAPACITY: u32 = 256;
+
+/// Sends messages to main worker, through [`InputRing`] if possible.
+///
+/// Messages through the ring are handled at the next frame, so they may be handled after messages
+/// posted later.
+struct Sender {
+    worker: Worker,
+    ring: Option<InputRing>,
+}
+
+impl Sender {
+    fn send(&self, msg: &JsMessage) {
+        if self.ring.as_ref().is_some_and(|ring| ring.push(msg)) {
+            return;
+        }
+        self.worker.post_message(&msg.to_js()).unwrap();
+    }
+}
+
+/// Registers an event listener on `target` sending the message made from the event to `sender`.
+///
+/// The listener is not passive, so it can prevent default behavior of the event.
+fn add_proxy<E, F>(
+    target: &web_sys::EventTarget,
+    event_type: &str,
+    sender: &Rc<Sender>,
+    to_message: F,
+) -> Proxy
+where
+    E: JsCast + 'static,
+    F: Fn(E, f64) -> JsMessage + 'static,
+{
+    let sender = Rc::clone(sender);
+    let listener = Closure::<dyn Fn(_)>::new(move |event: web_sys::Event| {
+        let window = web_sys::window().unwrap();
+        let scale_factor = window.device_pixel_ratio();
+        sender.send(&to_message(event.unchecked_into(), scale_factor));
+    });
+    Proxy::new(target, event_type, listener)
+}
+
+/// An event listener, which is removed when dropped.
+struct Proxy {
+    target: web_sys::EventTarget,
+    event_type: String,
+    listener: Closure<dyn Fn(web_sys::Event)>,
+}
+
+impl Proxy {
+    /// Registers the listener. It's not passive, so it can prevent default behavior of the event.
+    fn new(
+        target: &web_sys::EventTarget,
+        event_type: &str,
+        listener: Closure<dyn Fn(web_sys::Event)>,
+    ) -> Self {
+        let options = web_sys::AddEventListenerOptions::new();
+        options.set_passive(false);
+        target
+            .add_event_listener_with_callback_and_add_event_listener_options(
+                event_type,
+                listener.as_ref().unchecked_ref(),
+                &options,
+            )
             .unwrap();
-        listener.forget(); // Leak, but just once.
+        Self {
+            target: target.clone(),
+            event_type: event_type.to_owned(),
+            listener,
+        }
+    }
+}
 
-        Self { canvas, worker }
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        let _ = self.target.remove_event_listener_with_callback(
+            &self.event_type,
+            self.listener.as_ref().unchecked_ref(),
+        );
     }
 }
 
 thread_local! {
-    /// Main render state.
-    static STATE: RefCell<State> = panic!();
+    /// Main render state. `None` before `Init`, while `Reinit` and after `Shutdown`.
+    static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
+
+    /// Messages arrived while [`STATE`] is being initialized, which are handled once it's created.
+    /// Canvases and the ring of a failed initialization are kept here as well for the next try.
+    static PENDING: RefCell<Vec<JsMessage>> = const { RefCell::new(Vec::new()) };
+
+    /// True while [`init`] is creating [`STATE`].
+    static INITIALIZING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Error handling a message while [`STATE`] doesn't exist.
+#[derive(Debug)]
+pub struct NotInitialized;
+
+impl fmt::Display for NotInitialized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("main worker is not initialized")
+    }
+}
+
+impl std::error::Error for NotInitialized {}
+
+/// Calls `f` with [`STATE`].
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> Result<R, NotInitialized> {
+    STATE.with_borrow_mut(|state| state.as_mut().map(f).ok_or(NotInitialized))
 }
 
 /// Initializes [`STATE`] from worker side, not in window context.
 /// That's because window and worker don't share memory (We can use shared memory with some restrictions).
-/// When initialization is over, JS replaces this event handler with [`main_onmessage`].
+/// When initialization succeeds, JS replaces this event handler with [`main_onmessage`].
+/// Otherwise, this keeps handling messages, and `Reinit` tries again.
 #[wasm_bindgen]
 pub async fn main_onmessage_init(event: web_sys::MessageEvent) -> bool {
-    let data = event.data();
-    debug_assert!(data.is_array());
-    let data: js_sys::Array = data.unchecked_into();
-    match JsMessage::from_f64(data.get(0)).0 {
-        JsMessage::INIT_INNER => {
-            // Initializes State.
-            let canvas: web_sys::OffscreenCanvas = data.get(1).unchecked_into();
-            let handle = data.get(2).as_f64().unwrap() as u32;
-            let canvas = OffscreenCanvas::new(canvas, handle);
-            let state = State::new(canvas).await;
-            STATE.set(state);
-
-            // Registers animation callback to the State and activate it.
-            let animation_cb = Closure::<dyn FnMut(f32)>::new(move |time: f32| {
-                STATE.with_borrow_mut(|state| {
-                    state.render(time);
-                    state.request_animation_frame();
-                })
-            });
-            STATE.with_borrow_mut(move |state| {
-                state.animation_cb = animation_cb;
-                state.request_animation_frame();
-            });
-
-            // Ready.
-            true
+    match JsMessage::from_js(event.data()) {
+        Ok(JsMessage::Init | JsMessage::Reinit) if !INITIALIZING.get() => {
+            // Handles messages such as `AddCanvas` that came during initialization as well.
+            init(Vec::new(), None).await
+        }
+        // After a failed `Init`, nothing is pending to close it.
+        Ok(JsMessage::Shutdown) if !INITIALIZING.get() => {
+            on_message(JsMessage::Shutdown);
+            false
+        }
+        Ok(msg) => {
+            // Not ready yet. Messages keep coming here while `init` is pending.
+            PENDING.with_borrow_mut(|pending| pending.push(msg));
+            false
+        }
+        Err(e) => {
+            crate::log!("{e}");
+            false
         }
-        _ => false, // Not ready yet.
     }
 }
 
 /// Main worker's message handler for various events.
 #[wasm_bindgen]
 pub fn main_onmessage(event: web_sys::MessageEvent) {
-    let data = event.data();
-    debug_assert!(data.is_array());
-    let data: js_sys::Array = data.unchecked_into();
-    match JsMessage::from_f64(data.get(0)).0 {
-        JsMessage::WINDOW_RESIZE_INNER => {
-            STATE.with_borrow_mut(|state| {
-                state.resize(JsResizeMessage::from_js_array(data, 1));
-            });
+    match JsMessage::from_js(event.data()) {
+        Ok(msg) => on_message(msg),
+        Err(e) => {
+            crate::log!("{e}");
         }
-        JsMessage::MOUSE_MOVE_INNER => {
-            STATE.with_borrow_mut(|state| {
-                state.mouse_move(JsMouseMessage::from_js_array(data, 1));
-            });
+    }
+}
+
+fn on_message(msg: JsMessage) {
+    match msg {
+        JsMessage::Shutdown => {
+            if let Some(state) = STATE.take() {
+                state.shutdown();
+            }
+            reply(&JsReply::Shutdown);
+            let global = js_sys::global().unchecked_into::<web_sys::DedicatedWorkerGlobalScope>();
+            global.close();
         }
-        JsMessage::MOUSE_CLICK_INNER => {
-            STATE.with_borrow_mut(|state| {
-                state.mouse_click(JsMouseMessage::from_js_array(data, 1));
+        JsMessage::Reinit => match STATE.take() {
+            Some(state) => {
+                let (canvases, ring) = state.shutdown();
+                wasm_bindgen_futures::spawn_local(async {
+                    init(canvases, ring).await;
+                });
+            }
+            // The last `Reinit` failed, then tries again with the canvases kept in `PENDING`.
+            None if !INITIALIZING.get() => {
+                wasm_bindgen_futures::spawn_local(async {
+                    init(Vec::new(), None).await;
+                });
+            }
+            None => {
+                crate::log!("{NotInitialized}");
+            }
+        },
+        msg => {
+            let unhandled = STATE.with_borrow_mut(|state| match state {
+                Some(state) => {
+                    state.on_message(msg);
+                    None
+                }
+                None => Some(msg),
             });
+            match unhandled {
+                None => {}
+                // Canvases and buffers were transferred, so window can't send them again.
+                // Keeps them for the state `Reinit` is creating.
+                Some(
+                    msg @ (JsMessage::AddCanvas { .. }
+                    | JsMessage::SetMesh { .. }
+                    | JsMessage::AttachRing { .. }),
+                ) => PENDING.with_borrow_mut(|pending| pending.push(msg)),
+                // Input can be dropped.
+                Some(_) => {
+                    crate::log!("{NotInitialized}");
+                }
+            }
         }
-        other => {
-            crate::log!("unsupported message: {:?}", other);
+    }
+}
+
+/// Creates [`STATE`] drawing onto the given canvases, and starts drawing.
+/// Then handles [`PENDING`] messages, which came while it was being created.
+///
+/// Returns false if it failed. Then the canvases and the ring stay in [`PENDING`] for the next
+/// try, and a pending `Shutdown` closes the worker.
+async fn init(canvases: Vec<OffscreenCanvas>, ring: Option<InputRing>) -> bool {
+    INITIALIZING.set(true);
+    let result = State::new().await;
+    INITIALIZING.set(false);
+    let mut state = match result {
+        Ok(state) => state,
+        Err(e) => {
+            // Transferred canvases and memory can't be sent again, so keeps them ahead of the
+            // pending messages such as `SetMesh` for them.
+            let kept = canvases
+                .into_iter()
+                .map(|canvas| {
+                    let (canvas, handle) = canvas.each();
+                    JsMessage::AddCanvas { canvas, handle }
+                })
+                .chain(ring.map(|ring| JsMessage::AttachRing {
+                    buffer: ring.buffer().clone(),
+                }));
+            let mut shutdown = false;
+            PENDING.with_borrow_mut(|pending| {
+                let queued = std::mem::take(pending);
+                pending.extend(kept);
+                for msg in queued {
+                    match msg {
+                        JsMessage::Shutdown => shutdown = true,
+                        // Tries again anyway.
+                        JsMessage::Init | JsMessage::Reinit => {}
+                        msg => pending.push(msg),
+                    }
+                }
+            });
+
+            let message = e.to_string();
+            reply(&JsReply::InitError { message });
+            if shutdown {
+                on_message(JsMessage::Shutdown);
+            }
+            return false;
         }
+    };
+    for canvas in canvases {
+        state.add_canvas(canvas);
+    }
+    state.ring = ring;
+
+    // Registers animation callback to the State and activate it.
+    state.animation_cb = Closure::<dyn FnMut(f32)>::new(move |time: f32| {
+        // Not initialized after shutdown, then stops.
+        let _ = with_state(|state| {
+            if state.context.is_lost() {
+                // Pauses the loop until a new device is ready.
+                state.animation_id = None;
+                wasm_bindgen_futures::spawn_local(recover());
+                return;
+            }
+            state.render(time);
+            state.request_animation_frame();
+        });
+    });
+    state.request_animation_frame();
+    STATE.set(Some(state));
+    for msg in PENDING.take() {
+        on_message(msg);
     }
+
+    reply(&JsReply::Initialized);
+    true
+}
+
+/// Recreates the device after it's lost, moves all canvases onto it and resumes drawing.
+/// The loop tries again on the next frame if no device is available yet.
+async fn recover() {
+    let Ok((instance, context)) =
+        with_state(|state| (state.instance.clone(), state.context.clone()))
+    else {
+        return;
+    };
+    // WebGPU doesn't need a surface to find an adapter.
+    let result = context.recreate(&instance, None).await;
+    let _ = with_state(|state| {
+        // Reinitialized meanwhile, which has made a new device already.
+        if !state.context.is_lost() {
+            return;
+        }
+        match result {
+            Ok(context) => state.recover(context),
+            Err(e) => {
+                crate::log!("failed to recover: {e}");
+            }
+        }
+        state.request_animation_frame();
+    });
+}
+
+/// Posts a reply to the window.
+fn reply(reply: &JsReply) {
+    let global = js_sys::global().unchecked_into::<web_sys::DedicatedWorkerGlobalScope>();
+    global.post_message(&reply.to_js()).unwrap();
 }
 
 /// Drawing relative data.
 /// Note that this belongs to main worker.
 struct State {
-    canvas: OffscreenCanvas,
-    surface: wgpu::Surface<'static>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    surface_config: wgpu::SurfaceConfiguration,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    index_num: u32,
-    uniform_data: UniformData,
-    uniform_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
-    render_pipeline: wgpu::RenderPipeline,
+    instance: wgpu::Instance,
+    /// Shared by all canvases.
+    context: Context,
+    shader_module: wgpu::ShaderModule,
+    /// WGSL source of `shader_module`, which recovery builds modules from.
+    shader_source: String,
+    /// Canvases by handle.
+    views: BTreeMap<u32, View>,
+    /// Handle of the canvas last pressed, which receives keyboard messages.
+    active: Option<u32>,
+    /// Input from window in shared memory, if window shares it.
+    ring: Option<InputRing>,
+    timer: FrameTimer,
     animation_cb: Closure<dyn FnMut(f32)>,
+    /// Request id of the next animation frame.
+    animation_id: Option<i32>,
 }
 
 impl State {
-    pub async fn new(canvas: OffscreenCanvas) -> Self {
+    pub async fn new() -> Result<Self, Error> {
         // wgpu instance
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::BROWSER_WEBGPU,
             ..Default::default()
         });
-        // wgpu surface
-        let surface = instance
-            .create_surface(wgpu::SurfaceTarget::OffscreenCanvas(
-                web_sys::OffscreenCanvas::clone(&canvas),
-            ))
-            .unwrap();
-        // wgpu adapter
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-                ..Default::default()
-            })
-            .await
-            .unwrap();
-        // wgpu device and queue
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::downlevel_webgl2_defaults(),
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .unwrap();
-        // wgpu surface configuration
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_caps.formats[0],
-            width: canvas.width(),
-            height: canvas.height(),
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
-        log!(
-            "suface size: {} x {}",
-            surface_config.width,
-            surface_config.height
-        );
-        surface.configure(&device, &surface_config);
-        // wgpu vertex buffer
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex buffer"),
-            contents: bytemuck::cast_slice(VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        // wgpu index buffer
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index buffer"),
-            contents: bytemuck::cast_slice(INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-        // wgpu uniform buffer
-        let uniform_data = UniformData {
-            resolution: [canvas.width() as f32, canvas.height() as f32],
-            mouse_move: [std::f32::MIN, std::f32::MIN],
-            mouse_click: [std::f32::MIN, std::f32::MIN],
-            ..Default::default()
-        };
-        let (uniform_buffer, uniform_layout, uniform_bind_group) =
-            State::create_uniform_buffer(&device, bytemuck::cast_slice(&[uniform_data][..]));
+        // wgpu adapter, device and queue
+        // WebGPU doesn't need a surface to find an adapter, so canvases can be added later.
+        let context =
+            Context::new(&instance, None, wgpu::Limits::downlevel_webgl2_defaults()).await?;
+        watch_device(&context);
         // wgpu shader module
-        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader module"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("example.wgsl").into()),
-        });
-        // wgpu render pipeline
-        let render_pipeline = State::create_render_pipeline(
-            &device,
-            &[&uniform_layout],
-            &shader_module,
-            &surface_config,
-        );
+        let shader_source = include_str!("example.wgsl").to_owned();
+        let shader_module = create_shader_module(&context.device, &shader_source);
         // dummy animation callback.
         let animation_cb = Closure::<dyn FnMut(f32)>::new(|_| {});
 
-        Self {
-            canvas,
-            surface,
-            device,
-            queue,
-            surface_config,
-            vertex_buffer,
-            index_buffer,
-            index_num: INDICES.len() as u32,
-            uniform_data,
-            uniform_buffer,
-            uniform_bind_group,
-            render_pipeline,
+        Ok(Self {
+            instance,
+            context,
+            shader_module,
+            shader_source,
+            views: BTreeMap::new(),
+            active: None,
+            ring: None,
+            timer: FrameTimer::default(),
             animation_cb,
+            animation_id: None,
+        })
+    }
+
+    pub fn request_animation_frame(&mut self) {
+        let global = js_sys::global().unchecked_into::<web_sys::DedicatedWorkerGlobalScope>();
+        let id = global
+            .request_animation_frame(self.animation_cb.as_ref().unchecked_ref())
+            .unwrap();
+        self.animation_id = Some(id);
+    }
+
+    /// Stops drawing and releases GPU resources in order, surfaces and buffers before the device.
+    /// Returns what can be used again by a new state.
+    fn shutdown(self) -> (Vec<OffscreenCanvas>, Option<InputRing>) {
+        let Self {
+            instance,
+            context,
+            shader_module,
+            views,
+            ring,
+            animation_id,
+            ..
+        } = self;
+
+        if let Some(id) = animation_id {
+            let global = js_sys::global().unchecked_into::<web_sys::DedicatedWorkerGlobalScope>();
+            global.cancel_animation_frame(id).unwrap();
         }
+        let canvases = views.into_values().map(|view| view.canvas).collect();
+        drop(shader_module);
+        context.device.destroy();
+        drop(context);
+        drop(instance);
+        log!("main worker shut down");
+
+        (canvases, ring)
     }
 
-    fn create_uniform_buffer(
-        device: &wgpu::Device,
-        contents: &[u8],
-    ) -> (wgpu::Buffer, wgpu::BindGroupLayout, wgpu::BindGroup) {
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Uniform buffer"),
-            contents,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Uniform bind group layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Uniform bind group"),
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-        });
-        (buffer, bind_group_layout, bind_group)
-    }
-
-    fn create_render_pipeline(
-        device: &wgpu::Device,
-        bind_group_layouts: &[&wgpu::BindGroupLayout],
-        shader_module: &wgpu::ShaderModule,
-        surface_config: &wgpu::SurfaceConfiguration,
-    ) -> wgpu::RenderPipeline {
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render pipeline layout"),
-                bind_group_layouts,
-                push_constant_ranges: &[],
-            });
-        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: shader_module,
-                entry_point: "v_main",
-                buffers: &[Vertex::layout()],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
+    /// Moves onto a context recreated after the device is lost.
+    /// Canvases that can't be drawn onto anymore reply [`JsReply::SurfaceError`].
+    fn recover(&mut self, context: Context) {
+        watch_device(&context);
+        self.shader_module = create_shader_module(&context.device, &self.shader_source);
+        for (&handle, view) in self.views.iter_mut() {
+            view.surface_error = None;
+            if let Err(e) = view.renderer.recover(context.clone(), &self.shader_module) {
+                let message = e.to_string();
+                view.surface_error = Some(message.clone());
+                reply(&JsReply::SurfaceError { handle, message });
+            }
+        }
+        self.context = context;
+        log!("main worker recovered from device loss");
+        reply(&JsReply::DeviceRecovered);
+    }
+
+    pub fn render(&mut self, time: f32) {
+        // Handles input written into the ring since the last frame.
+        let messages = self.ring.as_ref().map(InputRing::drain).unwrap_or_default();
+        for msg in messages {
+            match msg {
+                Ok(msg) => self.on_message(msg),
+                Err(e) => {
+                    crate::log!("{e}");
+                }
+            }
+        }
+
+        if let Some(stats) = self.timer.tick(time as f64) {
+            reply(&stats);
+        }
+        for (&handle, view) in self.views.iter_mut() {
+            view.controls.update(&view.input);
+            view.renderer.apply_input(&view.input);
+            let resolution = view.renderer.uniform.data.resolution;
+            view.renderer.uniform.data.view = view.controls.view(resolution);
+            let error = view.renderer.try_render(time).err().map(|e| e.to_string());
+            view.input.end_frame();
+            // Reports only changes, not to flood the window every frame.
+            if error != view.surface_error {
+                if let Some(message) = &error {
+                    let message = message.clone();
+                    reply(&JsReply::SurfaceError { handle, message });
+                }
+                view.surface_error = error;
+            }
+        }
+    }
+
+    fn on_message(&mut self, msg: JsMessage) {
+        match msg {
+            JsMessage::AddCanvas { canvas, handle } => {
+                self.add_canvas(OffscreenCanvas::new(canvas, handle));
+            }
+            JsMessage::AttachRing { buffer } => {
+                self.ring = Some(InputRing::from_buffer(buffer));
+            }
+            JsMessage::SetMesh {
+                handle,
+                vertices,
+                indices,
+            } => {
+                if let Some(view) = self.view_mut(handle) {
+                    match read_mesh(&vertices, &indices) {
+                        Ok((vertices, indices)) => view.set_mesh(&vertices, &indices),
+                        Err(e) => {
+                            crate::log!("{e}");
+                        }
+                    }
+                }
+            }
+            JsMessage::SetShader { source } => self.reload_shader(source),
+            JsMessage::WindowResize(msg) => {
+                if let Some(view) = self.view_mut(msg.handle) {
+                    view.resize(msg);
+                }
+            }
+            JsMessage::MouseMove(msg) => {
+                if let Some(view) = self.view_mut(msg.handle) {
+                    view.mouse_move(msg);
+                }
+            }
+            JsMessage::MouseClick(msg) => {
+                if let Some(view) = self.view_mut(msg.handle) {
+                    view.mouse_click(msg);
+                }
+            }
+            JsMessage::KeyDown(msg) => {
+                if let Some(view) = self.active.and_then(|handle| self.views.get_mut(&handle)) {
+                    view.input.key_down(&msg.code);
+                }
+            }
+            JsMessage::KeyUp(msg) => {
+                if let Some(view) = self.active.and_then(|handle| self.views.get_mut(&handle)) {
+                    view.input.key_up(&msg.code);
+                }
+            }
+            JsMessage::Wheel(msg) => {
+                if let Some(view) = self.view_mut(msg.handle) {
+                    let [dx, dy] = msg.delta_pixels();
+                    view.input.scroll(dx as f32, dy as f32);
+                }
+            }
+            JsMessage::PointerDown(msg) => {
+                self.active = Some(msg.handle);
+                // Touches are handled by touch messages, not to drag twice.
+                if msg.pointer_type == "touch" {
+                    return;
+                }
+                if let Some(view) = self.view_mut(msg.handle) {
+                    view.input.buttons = msg.buttons;
+                }
+            }
+            JsMessage::PointerUp(msg) => {
+                if msg.pointer_type == "touch" {
+                    return;
+                }
+                if let Some(view) = self.view_mut(msg.handle) {
+                    view.input.buttons = msg.buttons;
+                }
+            }
+            JsMessage::PointerCancel(msg) => {
+                if msg.pointer_type == "touch" {
+                    return;
+                }
+                if let Some(view) = self.view_mut(msg.handle) {
+                    view.input.buttons = 0;
+                }
+            }
+            JsMessage::TouchStart(msg) => {
+                self.active = Some(msg.handle);
+                if let Some(view) = self.view_mut(msg.handle) {
+                    view.touch_start(msg);
+                }
+            }
+            JsMessage::TouchMove(msg) => {
+                if let Some(view) = self.view_mut(msg.handle) {
+                    view.touch_move(msg);
+                }
+            }
+            JsMessage::TouchEnd(msg) | JsMessage::TouchCancel(msg) => {
+                if let Some(view) = self.view_mut(msg.handle) {
+                    view.controls.touch_end(&msg);
+                }
+            }
+            // Lifecycle messages are handled without the state.
+            JsMessage::Init | JsMessage::Shutdown | JsMessage::Reinit => {
+                crate::log!("unexpected message: {:?}", msg);
+            }
+        }
+    }
+
+    /// Replies [`JsReply::SurfaceError`] if the canvas can't be drawn onto.
+    fn add_canvas(&mut self, canvas: OffscreenCanvas) {
+        let handle = canvas.handle();
+        match self.create_renderer(&canvas) {
+            Ok(renderer) => {
+                log!(
+                    "suface {} size: {} x {}",
+                    handle,
+                    renderer.target.width(),
+                    renderer.target.height()
+                );
+                let view = View {
+                    canvas,
+                    renderer,
+                    controls: Controls::default(),
+                    input: Input::new(),
+                    surface_error: None,
+                };
+                self.views.insert(handle, view);
+            }
+            Err(e) => {
+                let message = e.to_string();
+                reply(&JsReply::SurfaceError { handle, message });
+            }
+        }
+    }
+
+    fn create_renderer(&self, canvas: &OffscreenCanvas) -> Result<Renderer, Error> {
+        // wgpu surface
+        let surface = self
+            .instance
+            .create_surface(wgpu::SurfaceTarget::OffscreenCanvas(
+                web_sys::OffscreenCanvas::clone(canvas),
+            ))?;
+        // wgpu surface configuration, buffers and render pipeline on the shared device
+        Renderer::new(
+            self.context.clone(),
+            surface,
+            &RendererDescriptor {
+                vertices: QUAD_VERTICES,
+                indices: QUAD_INDICES,
+                shader_module: &self.shader_module,
+                width: canvas.width(),
+                height: canvas.height(),
+                scale: 1.0, // Mouse positions are scaled before they reach the shader.
             },
-            fragment: Some(wgpu::FragmentState {
-                module: shader_module,
-                entry_point: "f_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-        })
+        )
     }
 
-    fn render(&mut self, time: f32) {
-        // Write uniform data to its buffer
-        self.uniform_data.time = time * 0.001;
-        self.queue.write_buffer(
-            &self.uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[self.uniform_data][..]),
-        );
+    /// Rebuilds the pipelines of all canvases from the WGSL source, then replies
+    /// [`JsReply::ShaderReloaded`] or [`JsReply::ShaderError`].
+    /// Pipelines are replaced only if all of them are valid.
+    fn reload_shader(&self, source: String) {
+        if self.views.is_empty() {
+            let message = "no canvas to compile the shader for".to_owned();
+            reply(&JsReply::ShaderError { message });
+            return;
+        }
+        let pending = self
+            .views
+            .iter()
+            .map(|(&handle, view)| {
+                let wgsl = wgpu::ShaderSource::Wgsl(source.as_str().into());
+                (handle, view.renderer.create_pipeline(wgsl))
+            })
+            .collect::<Vec<_>>();
+        let device = self.context.device.clone();
 
-        let surface_texture = self.surface.get_current_texture().unwrap();
-        let texture_view = surface_texture.texture.create_view(&Default::default());
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render command encoder"),
+        wasm_bindgen_futures::spawn_local(async move {
+            let mut pipelines = Vec::with_capacity(pending.len());
+            for (handle, pipeline) in pending {
+                match pipeline.await {
+                    Ok(pipeline) => pipelines.push((handle, pipeline)),
+                    Err(e) => {
+                        let message = e.to_string();
+                        reply(&JsReply::ShaderError { message });
+                        return;
+                    }
+                }
+            }
+            let applied = with_state(|state| {
+                // Recovered from device loss meanwhile, so builds them again on the new device.
+                if state.context.device != device {
+                    state.reload_shader(source);
+                    return false;
+                }
+                for (handle, pipeline) in pipelines {
+                    // The canvas may have gone meanwhile.
+                    if let Some(view) = state.views.get_mut(&handle) {
+                        view.renderer.render_pipeline = pipeline;
+                    }
+                }
+                state.shader_module = create_shader_module(&state.context.device, &source);
+                state.shader_source = source;
+                true
             });
+            if let Ok(true) = applied {
+                reply(&JsReply::ShaderReloaded);
+            }
+        });
+    }
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &texture_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.draw_indexed(0..self.index_num, 0, 0..1);
+    fn view_mut(&mut self, handle: u32) -> Option<&mut View> {
+        let view = self.views.get_mut(&handle);
+        if view.is_none() {
+            crate::log!("unknown canvas handle: {handle}");
         }
-        self.queue.submit(std::iter::once(encoder.finish()));
-        surface_texture.present();
+        view
     }
+}
 
-    pub fn request_animation_frame(&self) {
-        let global = js_sys::global().unchecked_into::<web_sys::DedicatedWorkerGlobalScope>();
-        global
-            .request_animation_frame(self.animation_cb.as_ref().unchecked_ref())
-            .unwrap();
+/// Reports errors and device loss to the window.
+/// The animation loop recovers lost devices.
+fn watch_device(context: &Context) {
+    context.watch(|event| match event {
+        DeviceEvent::Lost { reason, message } => {
+            let reason = format!("{reason:?}");
+            reply(&JsReply::DeviceLost { reason, message });
+        }
+        DeviceEvent::Error(error) => {
+            let message = match error {
+                wgpu::Error::Validation { description, .. }
+                | wgpu::Error::Internal { description, .. } => description.clone(),
+                other => other.to_string(),
+            };
+            reply(&JsReply::DeviceError { message });
+        }
+    });
+}
+
+fn create_shader_module(device: &wgpu::Device, source: &str) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader module"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
+
+/// A canvas and what is drawn onto it.
+struct View {
+    canvas: OffscreenCanvas,
+    renderer: Renderer,
+    controls: Controls,
+    /// Pointer state written to the uniform data each frame.
+    input: Input,
+    /// The last error the surface gave.
+    surface_error: Option<String>,
+}
+
+impl View {
+    fn set_mesh(&mut self, vertices: &[renderer::Vertex], indices: &[u32]) {
+        let device = &self.renderer.context.device;
+        self.renderer.mesh = Mesh::new(device, vertices, indices);
     }
 
     fn resize(&mut self, msg: JsResizeMessage) {
-        let new_width = (msg.width * msg.scale_factor) as u32;
-        let new_height = (msg.height * msg.scale_factor) as u32;
+        let new_width = (msg.width as f64 * msg.scale_factor) as u32;
+        let new_height = (msg.height as f64 * msg.scale_factor) as u32;
         if new_width != self.canvas.width() || new_height != self.canvas.height() {
-            self.surface_config.width = new_width;
-            self.surface_config.height = new_height;
-            self.surface.configure(&self.device, &self.surface_config);
-
-            // Update uniform data
-            self.uniform_data.resolution = [new_width as f32, new_height as f32];
+            self.renderer.resize(new_width, new_height);
 
             log!(
-                "Resized: ({}, {}), scale: {}",
+                "Resized {}: ({}, {}), scale: {}",
+                msg.handle,
                 new_width,
                 new_height,
                 msg.scale_factor
@@ -440,82 +1026,38 @@ impl State {
     }
 
     pub fn mouse_move(&mut self, msg: JsMouseMessage) {
-        // Update uniform data
         let x = (msg.offset_x * msg.scale_factor) as f32;
         let y = (msg.offset_y * msg.scale_factor) as f32;
-        self.uniform_data.mouse_move = [x, y];
+        self.input.pointer_move(x, y);
     }
 
     pub fn mouse_click(&mut self, msg: JsMouseMessage) {
-        // Update uniform data
         let x = (msg.offset_x * msg.scale_factor) as f32;
         let y = (msg.offset_y * msg.scale_factor) as f32;
-        self.uniform_data.mouse_click = [x, y];
+        self.input.click(x, y);
     }
-}
 
-/// Simple vertex format.
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    pos: [f32; 3],
-    color: [f32; 3],
-}
+    pub fn touch_start(&mut self, msg: JsTouchMessage) {
+        self.controls.touch_start(&msg);
 
-impl Vertex {
-    fn layout() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    // pos
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    // color
-                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-            ],
+        // Touching is a click.
+        if let Some(touch) = msg.changed.first() {
+            let x = (touch.offset_x * msg.scale_factor) as f32;
+            let y = (touch.offset_y * msg.scale_factor) as f32;
+            self.input.click(x, y);
         }
     }
-}
 
-/// Vertex example.
-const VERTICES: &[Vertex] = &[
-    Vertex {
-        pos: [-1.0, 1.0, 0.0],  // Top-left
-        color: [1.0, 0.0, 1.0], // Magenta
-    },
-    Vertex {
-        pos: [-1.0, -1.0, 0.0], // Bottom-left
-        color: [0.0, 0.0, 1.0], // Blue
-    },
-    Vertex {
-        pos: [1.0, 1.0, 0.0],   // Top-right
-        color: [1.0, 1.0, 0.0], // Yellow
-    },
-    Vertex {
-        pos: [1.0, -1.0, 0.0],  // Bottom-right
-        color: [0.0, 1.0, 0.0], // Green
-    },
-];
-
-const INDICES: &[u32] = &[0, 1, 2, 2, 1, 3]; // CCW, quad
-
-/// Simple uniform data.
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Default)]
-struct UniformData {
-    mouse_move: [f32; 2],
-    mouse_click: [f32; 2],
-    resolution: [f32; 2],
-    time: f32,
-    _padding: f32,
+    pub fn touch_move(&mut self, msg: JsTouchMessage) {
+        self.controls.touch_move(&msg);
+
+        // Moving a finger is a mouse move.
+        if let Some(touch) = msg.touches.first() {
+            let x = (touch.offset_x * msg.scale_factor) as f32;
+            let y = (touch.offset_y * msg.scale_factor) as f32;
+            self.input.pointer_move(x, y);
+        }
+    }
 }
 
 /// Boilerplate initialization for wasm debugging.
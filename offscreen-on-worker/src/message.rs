@@ -1,139 +1,499 @@
+//! Messages between window and workers.
+//!
+//! A message is a [`JsMessage`] from window or a [`JsReply`] from main worker, or a [`JsJob`] and
+//! its [`JsJobResult`] between window and a compute worker, serialized into a plain JS object with
+//! serde, wrapped with [`PROTOCOL_VERSION`]. Decoding checks the version and every field, so
+//! malformed or unknown messages come out as [`MessageError`] instead of garbage.
+//!
+//! To forward a new event, add a variant to [`JsMessage`] and handle it in `main_onmessage`.
+
 use super::canvas::Canvas;
-use std::mem;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
 use wasm_bindgen::JsValue;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct JsMessage(pub u64);
+/// Wheel pixels per line, for `DOM_DELTA_LINE`.
+const LINE_HEIGHT: f64 = 16.0;
+/// Wheel pixels per page, for `DOM_DELTA_PAGE`.
+const PAGE_HEIGHT: f64 = 800.0;
 
-impl JsMessage {
-    /// Common message group.
-    const COMMON: u64 = 0;
-    /// Window message group.
-    const WINDOW: u64 = 1 << 32;
-    /// Mouse message group.
-    const MOUSE: u64 = 2 << 32;
+/// Version of the message protocol.
+/// Bump it whenever an existing message changes its shape.
+pub const PROTOCOL_VERSION: u32 = 2;
 
+#[derive(Debug, Serialize, Deserialize)]
+pub enum JsMessage {
     /// A common message requesting initialization of main object.
-    pub const INIT_INNER: u64 = Self::COMMON | 1;
-    pub const INIT: Self = Self(Self::INIT_INNER);
+    Init,
+    /// A common message releasing the surfaces and the device, then closing the worker.
+    Shutdown,
+    /// A common message recreating the device and the surfaces for the same canvases.
+    Reinit,
+    /// A common message adding a canvas to draw onto.
+    /// `canvas` must be transferred along with the message.
+    AddCanvas {
+        #[serde(with = "serde_wasm_bindgen::preserve")]
+        canvas: web_sys::OffscreenCanvas,
+        handle: u32,
+    },
+    /// A common message sharing [`InputRing`](super::ring::InputRing)'s memory.
+    /// After this, resize and mouse messages may come through the ring as well.
+    AttachRing {
+        #[serde(with = "serde_wasm_bindgen::preserve")]
+        buffer: js_sys::SharedArrayBuffer,
+    },
+    /// A common message replacing the mesh drawn onto the canvas of `handle`.
+    /// Buffers are laid out as [`JsOutput::Mesh`]'s and must be transferred along with the message.
+    SetMesh {
+        handle: u32,
+        #[serde(with = "serde_wasm_bindgen::preserve")]
+        vertices: js_sys::ArrayBuffer,
+        #[serde(with = "serde_wasm_bindgen::preserve")]
+        indices: js_sys::ArrayBuffer,
+    },
+    /// A common message replacing the shader of all canvases with WGSL source, for hot reloading.
+    SetShader { source: String },
 
     /// Window resize message.
-    pub const WINDOW_RESIZE_INNER: u64 = Self::WINDOW | 1;
-    pub const WINDOW_RESIZE: Self = Self(Self::WINDOW_RESIZE_INNER);
-
+    WindowResize(JsResizeMessage),
     /// Mouse move message.
-    pub const MOUSE_MOVE_INNER: u64 = Self::MOUSE | 1;
-    pub const MOUSE_MOVE: Self = Self(Self::MOUSE_MOVE_INNER);
+    MouseMove(JsMouseMessage),
     /// Mouse click message.
-    pub const MOUSE_CLICK_INNER: u64 = Self::MOUSE | 2;
-    pub const MOUSE_CLICK: Self = Self(Self::MOUSE_CLICK_INNER);
+    MouseClick(JsMouseMessage),
+
+    /// Key down message, which goes to the canvas last pressed.
+    KeyDown(JsKeyMessage),
+    /// Key up message.
+    KeyUp(JsKeyMessage),
 
-    /// Reinterprets value to f64 in bit level.
-    /// Then convert it into JsValue.
-    /// Use [`Self::from_f64()`] to recover.
-    #[inline]
-    pub fn into_jsvalue(self) -> JsValue {
-        let f: f64 = unsafe { mem::transmute(self) };
-        JsValue::from(f)
+    /// Wheel message.
+    Wheel(JsWheelMessage),
+
+    /// Pointer down message.
+    PointerDown(JsPointerMessage),
+    /// Pointer up message.
+    PointerUp(JsPointerMessage),
+    /// Pointer cancel message.
+    PointerCancel(JsPointerMessage),
+
+    /// Touch start message.
+    TouchStart(JsTouchMessage),
+    /// Touch move message.
+    TouchMove(JsTouchMessage),
+    /// Touch end message.
+    TouchEnd(JsTouchMessage),
+    /// Touch cancel message.
+    TouchCancel(JsTouchMessage),
+}
+
+impl JsMessage {
+    /// Converts message into JsValue that can be posted to the worker.
+    /// Use [`Self::from_js()`] to recover.
+    pub fn to_js(&self) -> JsValue {
+        encode(self)
     }
 
     /// Converts message given from JS side into original type.
-    #[inline]
-    pub fn from_f64(value: JsValue) -> Self {
-        let f = value.as_f64().unwrap();
-        Self(f.to_bits())
+    pub fn from_js(value: JsValue) -> Result<Self, MessageError> {
+        decode(value)
+    }
+}
+
+/// Messages from main worker to window.
+///
+/// `App` hands them to JS as objects such as `{ type: "frameStats", fps: 60, .. }`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum JsReply {
+    /// The worker has created its device and is ready to draw.
+    /// Comes again after `Reinit`.
+    Initialized,
+    /// The worker couldn't create its device, so nothing is drawn.
+    /// Comes instead of `Initialized`. `Reinit` tries again with the same canvases.
+    InitError { message: String },
+    /// The worker has released its device and is closing.
+    Shutdown,
+    /// Frame timing over about a second.
+    FrameStats {
+        /// Frames per second.
+        fps: f64,
+        /// Average time between frames in milliseconds.
+        frame_time: f64,
+        /// Longest time between frames in milliseconds.
+        max_frame_time: f64,
+    },
+    /// The canvas couldn't be drawn onto, or its surface couldn't give a texture so frames are
+    /// skipped.
+    SurfaceError { handle: u32, message: String },
+    /// An error not captured by any error scope.
+    DeviceError { message: String },
+    /// The device is lost and nothing is drawn until the worker recovers.
+    DeviceLost { reason: String, message: String },
+    /// The worker has recreated its device after `DeviceLost` and resumed drawing.
+    DeviceRecovered,
+    /// All canvases draw with the shader of `SetShader`.
+    ShaderReloaded,
+    /// The shader of `SetShader` is invalid, so the last good one keeps drawing.
+    ShaderError { message: String },
+}
+
+impl JsReply {
+    /// Converts reply into JsValue that can be posted to the window.
+    /// Use [`Self::from_js()`] to recover.
+    pub fn to_js(&self) -> JsValue {
+        encode(self)
+    }
+
+    /// Converts reply given from JS side into original type.
+    pub fn from_js(value: JsValue) -> Result<Self, MessageError> {
+        decode(value)
+    }
+}
+
+/// A task posted from window to a compute worker.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsJob {
+    /// Identifies the result.
+    pub id: u32,
+    pub task: JsTask,
+}
+
+impl JsJob {
+    /// Converts job into JsValue that can be posted to the worker.
+    /// Use [`Self::from_js()`] to recover.
+    pub fn to_js(&self) -> JsValue {
+        encode(self)
+    }
+
+    /// Converts job given from JS side into original type.
+    pub fn from_js(value: JsValue) -> Result<Self, MessageError> {
+        decode(value)
+    }
+}
+
+/// CPU tasks compute workers can run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JsTask {
+    /// Generates a grid of `columns` x `rows` quads covering the canvas.
+    GridMesh { columns: u32, rows: u32 },
+}
+
+/// Result of a [`JsJob`] posted from a compute worker to window.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsJobResult {
+    /// Id of the job.
+    pub id: u32,
+    /// Output or the reason the task failed.
+    pub output: Result<JsOutput, String>,
+}
+
+impl JsJobResult {
+    /// Converts result into JsValue that can be posted to the window.
+    /// Buffers in the output must be transferred along with it.
+    /// Use [`Self::from_js()`] to recover.
+    pub fn to_js(&self) -> JsValue {
+        encode(self)
+    }
+
+    /// Converts result given from JS side into original type.
+    pub fn from_js(value: JsValue) -> Result<Self, MessageError> {
+        decode(value)
+    }
+}
+
+/// Outputs of [`JsTask`]s.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum JsOutput {
+    /// `vertices` holds f32 of position and color, `[x, y, z, r, g, b]` for each vertex, and
+    /// `indices` holds u32 of triangles.
+    Mesh {
+        #[serde(with = "serde_wasm_bindgen::preserve")]
+        vertices: js_sys::ArrayBuffer,
+        #[serde(with = "serde_wasm_bindgen::preserve")]
+        indices: js_sys::ArrayBuffer,
+    },
+}
+
+impl JsOutput {
+    /// Buffers to be transferred instead of copied.
+    pub fn transfer(&self) -> js_sys::Array {
+        match self {
+            Self::Mesh { vertices, indices } => js_sys::Array::of2(vertices, indices),
+        }
+    }
+}
+
+fn encode<T: Serialize>(message: &T) -> JsValue {
+    let envelope = Envelope {
+        version: PROTOCOL_VERSION,
+        message,
+    };
+    // Infallible, all fields are representable in JS.
+    serde_wasm_bindgen::to_value(&envelope).unwrap()
+}
+
+fn decode<T: DeserializeOwned>(value: JsValue) -> Result<T, MessageError> {
+    // Checks version first, messages of other versions may not be decodable at all.
+    let Header { version } = serde_wasm_bindgen::from_value(value.clone())?;
+    if version != PROTOCOL_VERSION {
+        return Err(MessageError::Version(version));
+    }
+    let Body { message } = serde_wasm_bindgen::from_value(value)?;
+    Ok(message)
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    message: &'a T,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct Body<T> {
+    message: T,
+}
+
+/// Error decoding a message.
+#[derive(Debug)]
+pub enum MessageError {
+    /// Message of another protocol version.
+    Version(u32),
+    /// Not a message, unknown kind of message or a field missing or of a wrong type.
+    Malformed(serde_wasm_bindgen::Error),
+    /// Unknown kind of record in [`InputRing`](super::ring::InputRing).
+    Record(u32),
+}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Version(version) => write!(
+                f,
+                "message version {version} doesn't match {PROTOCOL_VERSION}"
+            ),
+            Self::Malformed(e) => write!(f, "malformed message: {e}"),
+            Self::Record(kind) => write!(f, "unknown kind of record: {kind}"),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+impl std::error::Error for MessageError {}
+
+impl From<serde_wasm_bindgen::Error> for MessageError {
+    fn from(value: serde_wasm_bindgen::Error) -> Self {
+        Self::Malformed(value)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsResizeMessage {
     pub scale_factor: f64,
-    pub handle: f64,
-    pub width: f64,
-    pub height: f64,
+    pub handle: u32,
+    pub width: i32,
+    pub height: i32,
 }
 
 impl JsResizeMessage {
-    /// Returns number of fileds.
-    /// Use this to prepare [`js_sys::Array`] buffer.
-    #[inline]
-    pub const fn field_num() -> u32 {
-        4
-    }
-
-    /// Helper
-    pub fn set_js_array(arr: &js_sys::Array, canvas: &Canvas, scale_factor: f64, offset: u32) {
-        arr.set(offset, JsValue::from(scale_factor));
-        arr.set(offset + 1, JsValue::from(canvas.handle()));
-        arr.set(offset + 2, JsValue::from(canvas.client_width()));
-        arr.set(offset + 3, JsValue::from(canvas.client_height()));
-    }
-
-    /// Helper
-    pub fn from_js_array(arr: js_sys::Array, offset: u32) -> Self {
-        // Safety: Infallible.
-        unsafe {
-            Self {
-                scale_factor: arr.get(offset).as_f64().unwrap_unchecked(),
-                handle: arr.get(offset + 1).as_f64().unwrap_unchecked(),
-                width: arr.get(offset + 2).as_f64().unwrap_unchecked(),
-                height: arr.get(offset + 3).as_f64().unwrap_unchecked(),
-            }
+    pub fn new(canvas: &Canvas, scale_factor: f64) -> Self {
+        Self {
+            scale_factor,
+            handle: canvas.handle(),
+            width: canvas.client_width(),
+            height: canvas.client_height(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsMouseMessage {
+    pub handle: u32,
     pub scale_factor: f64,
-    pub button: f64,
+    pub button: i16,
     pub client_x: f64,
     pub client_y: f64,
-    pub movement_x: f64,
-    pub movement_y: f64,
+    pub movement_x: i32,
+    pub movement_y: i32,
     pub offset_x: f64,
     pub offset_y: f64,
 }
 
 impl JsMouseMessage {
-    /// Returns number of fileds.
-    /// Use this to prepare [`js_sys::Array`] buffer.
-    #[inline]
-    pub const fn field_num() -> u32 {
-        8
-    }
-
-    /// Helper
-    pub fn set_js_array(
-        arr: &js_sys::Array,
-        event: web_sys::MouseEvent,
-        scale_factor: f64,
-        offset: u32,
-    ) {
-        arr.set(offset, JsValue::from(scale_factor));
-        arr.set(offset + 1, JsValue::from(event.button()));
-        arr.set(offset + 2, JsValue::from(event.client_x()));
-        arr.set(offset + 3, JsValue::from(event.client_y()));
-        arr.set(offset + 4, JsValue::from(event.movement_x()));
-        arr.set(offset + 5, JsValue::from(event.movement_y()));
-        arr.set(offset + 6, JsValue::from(event.offset_x()));
-        arr.set(offset + 7, JsValue::from(event.offset_y()));
-    }
-
-    /// Helper
-    pub fn from_js_array(arr: js_sys::Array, offset: u32) -> Self {
-        // Safety: Infallible.
-        unsafe {
-            Self {
-                scale_factor: arr.get(offset).as_f64().unwrap_unchecked(),
-                button: arr.get(offset + 1).as_f64().unwrap_unchecked(),
-                client_x: arr.get(offset + 2).as_f64().unwrap_unchecked(),
-                client_y: arr.get(offset + 3).as_f64().unwrap_unchecked(),
-                movement_x: arr.get(offset + 4).as_f64().unwrap_unchecked(),
-                movement_y: arr.get(offset + 5).as_f64().unwrap_unchecked(),
-                offset_x: arr.get(offset + 6).as_f64().unwrap_unchecked(),
-                offset_y: arr.get(offset + 7).as_f64().unwrap_unchecked(),
-            }
+    pub fn new(event: &web_sys::MouseEvent, handle: u32, scale_factor: f64) -> Self {
+        Self {
+            handle,
+            scale_factor,
+            button: event.button(),
+            client_x: event.client_x(),
+            client_y: event.client_y(),
+            movement_x: event.movement_x(),
+            movement_y: event.movement_y(),
+            offset_x: event.offset_x(),
+            offset_y: event.offset_y(),
+        }
+    }
+}
+
+/// Modifier keys held during an event.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct JsModifiers {
+    pub alt: bool,
+    pub ctrl: bool,
+    pub meta: bool,
+    pub shift: bool,
+}
+
+impl JsModifiers {
+    pub fn from_mouse(event: &web_sys::MouseEvent) -> Self {
+        Self {
+            alt: event.alt_key(),
+            ctrl: event.ctrl_key(),
+            meta: event.meta_key(),
+            shift: event.shift_key(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsKeyMessage {
+    /// Value of the key, e.g. "a" or "ArrowLeft".
+    pub key: String,
+    /// Physical key, e.g. "KeyA" or "ArrowLeft".
+    pub code: String,
+    pub repeat: bool,
+    pub modifiers: JsModifiers,
+}
+
+impl JsKeyMessage {
+    pub fn new(event: &web_sys::KeyboardEvent) -> Self {
+        Self {
+            key: event.key(),
+            code: event.code(),
+            repeat: event.repeat(),
+            modifiers: JsModifiers {
+                alt: event.alt_key(),
+                ctrl: event.ctrl_key(),
+                meta: event.meta_key(),
+                shift: event.shift_key(),
+            },
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsWheelMessage {
+    pub handle: u32,
+    pub scale_factor: f64,
+    pub delta_x: f64,
+    pub delta_y: f64,
+    /// One of `WheelEvent.DOM_DELTA_PIXEL`, `DOM_DELTA_LINE` and `DOM_DELTA_PAGE`.
+    pub delta_mode: u32,
+    pub offset_x: f64,
+    pub offset_y: f64,
+    pub modifiers: JsModifiers,
+}
+
+impl JsWheelMessage {
+    pub fn new(event: &web_sys::WheelEvent, handle: u32, scale_factor: f64) -> Self {
+        Self {
+            handle,
+            scale_factor,
+            delta_x: event.delta_x(),
+            delta_y: event.delta_y(),
+            delta_mode: event.delta_mode(),
+            offset_x: event.offset_x(),
+            offset_y: event.offset_y(),
+            modifiers: JsModifiers::from_mouse(event),
+        }
+    }
+
+    /// Returns the deltas in pixels, whichever `delta_mode` is.
+    pub fn delta_pixels(&self) -> [f64; 2] {
+        let scale = match self.delta_mode {
+            web_sys::WheelEvent::DOM_DELTA_LINE => LINE_HEIGHT,
+            web_sys::WheelEvent::DOM_DELTA_PAGE => PAGE_HEIGHT,
+            _ => 1.0,
+        };
+        [self.delta_x * scale, self.delta_y * scale]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsPointerMessage {
+    pub handle: u32,
+    pub scale_factor: f64,
+    pub pointer_id: i32,
+    /// "mouse", "pen" or "touch".
+    pub pointer_type: String,
+    pub is_primary: bool,
+    pub button: i16,
+    pub buttons: u16,
+    pub offset_x: f64,
+    pub offset_y: f64,
+    pub modifiers: JsModifiers,
+}
+
+impl JsPointerMessage {
+    pub fn new(event: &web_sys::PointerEvent, handle: u32, scale_factor: f64) -> Self {
+        Self {
+            handle,
+            scale_factor,
+            pointer_id: event.pointer_id(),
+            pointer_type: event.pointer_type(),
+            is_primary: event.is_primary(),
+            button: event.button(),
+            buttons: event.buttons(),
+            offset_x: event.offset_x(),
+            offset_y: event.offset_y(),
+            modifiers: JsModifiers::from_mouse(event),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsTouchMessage {
+    pub handle: u32,
+    pub scale_factor: f64,
+    /// Touches on the surface.
+    pub touches: Vec<JsTouch>,
+    /// Touches changed by this event. Ended touches are only here.
+    pub changed: Vec<JsTouch>,
+}
+
+impl JsTouchMessage {
+    pub fn new(event: &web_sys::TouchEvent, canvas: &Canvas, scale_factor: f64) -> Self {
+        // Touches have no offset, so makes one from the canvas position.
+        let rect = canvas.get_bounding_client_rect();
+        let to_vec = |list: web_sys::TouchList| {
+            (0..list.length())
+                .filter_map(|i| list.item(i))
+                .map(|touch| JsTouch {
+                    identifier: touch.identifier(),
+                    offset_x: touch.client_x() as f64 - rect.left(),
+                    offset_y: touch.client_y() as f64 - rect.top(),
+                })
+                .collect()
+        };
+        Self {
+            handle: canvas.handle(),
+            scale_factor,
+            touches: to_vec(event.touches()),
+            changed: to_vec(event.changed_touches()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct JsTouch {
+    pub identifier: i32,
+    pub offset_x: f64,
+    pub offset_y: f64,
+}
@@ -0,0 +1,186 @@
+//! Lock-free ring buffer carrying frequent input from window to main worker in shared memory.
+//!
+//! Window is the only producer and main worker is the only consumer. Both sides keep running
+//! counters in a header, and records in fixed-size slots after it.
+//!
+//! ```text
+//! | head: i32 | tail: i32 | slot 0: [f64; SLOT_LEN] | slot 1 | ... |
+//! ```
+//!
+//! Window writes a record into the slot at `head`, then stores `head + 1` with `Atomics.store()`.
+//! Main worker loads `head` with `Atomics.load()`, so the record is visible to it, reads records
+//! up to there, and then stores it to `tail` to free the slots.
+
+use super::message::*;
+use wasm_bindgen::JsValue;
+
+/// Number of f64 in a slot. The first one is a record kind.
+const SLOT_LEN: u32 = 10;
+const HEADER_SIZE: u32 = 8;
+const HEAD: u32 = 0;
+const TAIL: u32 = 1;
+
+/// Record kinds.
+const RESIZE: u32 = 1;
+const MOUSE_MOVE: u32 = 2;
+const MOUSE_CLICK: u32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct InputRing {
+    buffer: js_sys::SharedArrayBuffer,
+    header: js_sys::Int32Array,
+    slots: js_sys::Float64Array,
+    capacity: u32,
+}
+
+impl InputRing {
+    /// Returns true if the page can share memory with workers, which requires cross-origin
+    /// isolation.
+    pub fn is_available() -> bool {
+        js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("crossOriginIsolated"))
+            .is_ok_and(|isolated| isolated.is_truthy())
+    }
+
+    /// Allocates a ring of `capacity` records in shared memory.
+    /// `capacity` must be a power of two.
+    pub fn new(capacity: u32) -> Self {
+        assert!(capacity.is_power_of_two());
+        let size = HEADER_SIZE + capacity * SLOT_LEN * 8;
+        Self::from_buffer(js_sys::SharedArrayBuffer::new(size))
+    }
+
+    /// Views the ring another side allocated.
+    pub fn from_buffer(buffer: js_sys::SharedArrayBuffer) -> Self {
+        let capacity = (buffer.byte_length() - HEADER_SIZE) / (SLOT_LEN * 8);
+        let header = js_sys::Int32Array::new_with_byte_offset_and_length(&buffer, 0, 2);
+        let slots = js_sys::Float64Array::new_with_byte_offset_and_length(
+            &buffer,
+            HEADER_SIZE,
+            capacity * SLOT_LEN,
+        );
+        Self {
+            buffer,
+            header,
+            slots,
+            capacity,
+        }
+    }
+
+    /// Shared memory to be posted to the other side.
+    pub fn buffer(&self) -> &js_sys::SharedArrayBuffer {
+        &self.buffer
+    }
+
+    /// Writes the message into the ring. Called from window.
+    ///
+    /// Returns false if the ring is full, or the message can't be written into the ring. Then,
+    /// the message should be posted instead.
+    pub fn push(&self, msg: &JsMessage) -> bool {
+        let Some(record) = encode(msg) else {
+            return false;
+        };
+        // Only window writes `head`.
+        let head = self.header.get_index(HEAD) as u32;
+        let tail = self.load(TAIL);
+        if head.wrapping_sub(tail) >= self.capacity {
+            return false;
+        }
+
+        let offset = (head % self.capacity) * SLOT_LEN;
+        for (i, value) in record.into_iter().enumerate() {
+            self.slots.set_index(offset + i as u32, value);
+        }
+        self.store(HEAD, head.wrapping_add(1));
+        true
+    }
+
+    /// Reads all messages written into the ring. Called from main worker.
+    pub fn drain(&self) -> Vec<Result<JsMessage, MessageError>> {
+        // Only main worker writes `tail`.
+        let tail = self.header.get_index(TAIL) as u32;
+        let head = self.load(HEAD);
+
+        let mut messages = Vec::with_capacity(head.wrapping_sub(tail) as usize);
+        let mut record = [0.0; SLOT_LEN as usize];
+        let mut i = tail;
+        while i != head {
+            let offset = (i % self.capacity) * SLOT_LEN;
+            for (j, value) in record.iter_mut().enumerate() {
+                *value = self.slots.get_index(offset + j as u32);
+            }
+            messages.push(decode(&record));
+            i = i.wrapping_add(1);
+        }
+        self.store(TAIL, head);
+        messages
+    }
+
+    fn load(&self, index: u32) -> u32 {
+        // Infallible, it's an Int32Array in bounds.
+        js_sys::Atomics::load(&self.header, index).unwrap() as u32
+    }
+
+    fn store(&self, index: u32, value: u32) {
+        // Infallible, it's an Int32Array in bounds.
+        js_sys::Atomics::store(&self.header, index, value as i32).unwrap();
+    }
+}
+
+fn encode(msg: &JsMessage) -> Option<[f64; SLOT_LEN as usize]> {
+    let mouse = |kind: u32, msg: &JsMouseMessage| {
+        [
+            kind as f64,
+            msg.handle as f64,
+            msg.scale_factor,
+            msg.button as f64,
+            msg.client_x,
+            msg.client_y,
+            msg.movement_x as f64,
+            msg.movement_y as f64,
+            msg.offset_x,
+            msg.offset_y,
+        ]
+    };
+    match msg {
+        JsMessage::WindowResize(msg) => Some([
+            RESIZE as f64,
+            msg.handle as f64,
+            msg.scale_factor,
+            msg.width as f64,
+            msg.height as f64,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+        ]),
+        JsMessage::MouseMove(msg) => Some(mouse(MOUSE_MOVE, msg)),
+        JsMessage::MouseClick(msg) => Some(mouse(MOUSE_CLICK, msg)),
+        _ => None,
+    }
+}
+
+fn decode(record: &[f64; SLOT_LEN as usize]) -> Result<JsMessage, MessageError> {
+    let mouse = || JsMouseMessage {
+        handle: record[1] as u32,
+        scale_factor: record[2],
+        button: record[3] as i16,
+        client_x: record[4],
+        client_y: record[5],
+        movement_x: record[6] as i32,
+        movement_y: record[7] as i32,
+        offset_x: record[8],
+        offset_y: record[9],
+    };
+    match record[0] as u32 {
+        RESIZE => Ok(JsMessage::WindowResize(JsResizeMessage {
+            handle: record[1] as u32,
+            scale_factor: record[2],
+            width: record[3] as i32,
+            height: record[4] as i32,
+        })),
+        MOUSE_MOVE => Ok(JsMessage::MouseMove(mouse())),
+        MOUSE_CLICK => Ok(JsMessage::MouseClick(mouse())),
+        kind => Err(MessageError::Record(kind)),
+    }
+}
@@ -0,0 +1,46 @@
+use super::message::JsReply;
+
+/// How often frame stats are reported in milliseconds.
+const INTERVAL: f64 = 1000.0;
+
+/// Measures times between frames and makes [`JsReply::FrameStats`] about once a second.
+#[derive(Debug, Default)]
+pub struct FrameTimer {
+    /// Time of the last frame.
+    last: Option<f64>,
+    /// Time of the first frame in this interval.
+    since: f64,
+    frames: u32,
+    total: f64,
+    max: f64,
+}
+
+impl FrameTimer {
+    /// Records a frame drawn at `time` in milliseconds.
+    /// Returns stats if a second has passed since the last stats.
+    pub fn tick(&mut self, time: f64) -> Option<JsReply> {
+        let Some(last) = self.last.replace(time) else {
+            self.since = time;
+            return None;
+        };
+        let frame_time = time - last;
+        self.frames += 1;
+        self.total += frame_time;
+        self.max = self.max.max(frame_time);
+        if time - self.since < INTERVAL {
+            return None;
+        }
+
+        let stats = JsReply::FrameStats {
+            fps: self.frames as f64 * 1000.0 / (time - self.since),
+            frame_time: self.total / self.frames as f64,
+            max_frame_time: self.max,
+        };
+        *self = Self {
+            last: Some(time),
+            since: time,
+            ..Default::default()
+        };
+        Some(stats)
+    }
+}
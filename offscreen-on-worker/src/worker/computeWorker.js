@@ -0,0 +1,19 @@
+// Imports wasm statically like `mainWorker.js`.
+import * as wasm from '../../../..';
+
+export function attachCompute() {}
+
+onmessage = event => {
+  // Jobs can be posted right after the worker is created, so keeps them until wasm is ready.
+  const queue = [];
+  onmessage = event => queue.push(event);
+
+  // Initailzes wasm.
+  const { default: wbg_init } = wasm;
+  wbg_init(event.data[0]).then(() => {
+
+    // Now, worker is ready for jobs.
+    onmessage = wasm.compute_onmessage;
+    queue.forEach(wasm.compute_onmessage);
+  });
+}
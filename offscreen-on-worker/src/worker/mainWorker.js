@@ -6,19 +6,26 @@ import * as wasm from '../../../..';
 export function attachMain() {}
 
 onmessage = event => {
+  // `Init` and canvases are posted right after the worker is created, so keeps them until wasm is
+  // ready. Transferred canvases can't be posted again, so none of them may be dropped.
+  const queue = [];
+  onmessage = event => queue.push(event);
+
   // Initailzes wasm.
   const { default: wbg_init } = wasm;
   wbg_init(event.data[0]).then(() => {
 
     // Initializes our main worker.
-    onmessage = async (event) => {
+    // Messages other than `Init` are kept on wasm side until it's done.
+    const onmessage_init = async (event) => {
       const ready = await wasm.main_onmessage_init(event);
       if (ready) {
 
         // Now, worker is ready for work.
         onmessage = wasm.main_onmessage;
       }
-    }
+    };
+    onmessage = onmessage_init;
+    queue.forEach(onmessage_init);
   });
 }
-
@@ -1,5 +1,7 @@
 use std::{fmt::Debug, ops::Deref};
 use wasm_bindgen::prelude::*;
+mod pool;
+pub use pool::*;
 
 /// Binds JS.
 #[wasm_bindgen(module = "/src/worker/workerGen.js")]
@@ -19,40 +21,83 @@ extern "C" {
     fn attachMain();
 }
 
-pub struct MainWorker {
+/// Binds JS.
+/// Same as `attachMain`, but for `computeWorker.js`.
+#[wasm_bindgen(module = "/src/worker/computeWorker.js")]
+extern "C" {
+    /// Nothing to do.
+    #[wasm_bindgen]
+    fn attachCompute();
+}
+
+/// Kinds of workers `workerGen.js` can create.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerKind {
+    /// Owns the device and draws onto the canvases.
+    Main,
+    /// Runs CPU jobs such as mesh generation for [`WorkerPool`].
+    Compute,
+}
+
+impl WorkerKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Main => "main",
+            Self::Compute => "compute",
+        }
+    }
+}
+
+pub struct Worker {
     handle: web_sys::Worker,
+    kind: WorkerKind,
     name: String,
-    _callback: Closure<dyn FnMut(web_sys::Event)>,
+    _callback: Closure<dyn FnMut(web_sys::MessageEvent)>,
 }
 
-impl Drop for MainWorker {
-    /// Terminates web worker *immediately*.
+impl Drop for Worker {
+    /// Asks main worker to shut down. It releases its GPU resources in order, then closes itself.
+    /// Terminates it *immediately* if it can't be asked, and other kinds of workers always.
     fn drop(&mut self) {
+        // Nothing receives replies anymore.
+        self.handle.set_onmessage(None);
+
+        if self.kind == WorkerKind::Main {
+            let msg = crate::message::JsMessage::Shutdown.to_js();
+            if self.handle.post_message(&msg).is_ok() {
+                crate::log!("Worker({}) was asked to shut down", &self.name);
+                return;
+            }
+        }
         self.handle.terminate();
         crate::log!("Worker({}) was terminated", &self.name);
     }
 }
 
-impl Debug for MainWorker {
+impl Debug for Worker {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("MainWorker")
+        f.debug_struct("Worker")
             .field("handle", &self.handle)
+            .field("kind", &self.kind)
             .field("name", &self.name)
             .finish()
     }
 }
 
-impl MainWorker {
-    /// Spawns main worker from the window context.
-    pub fn spawn(name: &str, id: usize) -> Result<Self, JsValue> {
+impl Worker {
+    /// Spawns a worker of the given kind from the window context.
+    /// `onmessage` receives messages the worker posts.
+    pub fn spawn(
+        kind: WorkerKind,
+        name: &str,
+        id: usize,
+        onmessage: impl FnMut(web_sys::MessageEvent) + 'static,
+    ) -> Result<Self, JsValue> {
         // Creates a new worker.
-        let handle = create_worker("main", name);
+        let handle = create_worker(kind.as_str(), name);
 
-        // Sets default callback.
-        let callback = Closure::new(|_ev| {
-            // Implement if you want.
-            unimplemented!()
-        });
+        // Sets callback.
+        let callback = Closure::<dyn FnMut(web_sys::MessageEvent)>::new(onmessage);
         handle.set_onmessage(Some(callback.as_ref().unchecked_ref()));
 
         // Initializes the worker.
@@ -63,13 +108,14 @@ impl MainWorker {
 
         Ok(Self {
             handle,
+            kind,
             name: name.to_owned(),
             _callback: callback,
         })
     }
 }
 
-impl Deref for MainWorker {
+impl Deref for Worker {
     type Target = web_sys::Worker;
 
     #[inline]
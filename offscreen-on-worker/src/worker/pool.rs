@@ -0,0 +1,180 @@
+//! Pool of compute workers running CPU jobs away from window and main worker.
+//!
+//! [`WorkerPool::submit()`] posts a [`JsTask`] to the least busy worker and returns a [`Job`],
+//! which resolves to the worker's [`JsOutput`]. Outputs hold transferable buffers, so they can be
+//! handed to main worker without copying.
+
+use super::{Worker, WorkerKind};
+use crate::message::*;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+use wasm_bindgen::JsValue;
+
+/// Upper limit of [`WorkerPool::default_size()`].
+const MAX_POOL_SIZE: usize = 4;
+
+#[derive(Debug)]
+pub struct WorkerPool {
+    workers: Vec<Worker>,
+    jobs: Rc<RefCell<Jobs>>,
+    next_id: Cell<u32>,
+}
+
+impl WorkerPool {
+    /// Spawns `size` compute workers.
+    pub fn new(size: usize) -> Result<Self, JsValue> {
+        assert!(size > 0, "worker pool can't be empty");
+        let jobs = Rc::new(RefCell::new(Jobs {
+            pending: HashMap::new(),
+            loads: vec![0; size],
+        }));
+        let workers = (0..size)
+            .map(|i| {
+                let jobs = Rc::clone(&jobs);
+                let onmessage =
+                    move |event: web_sys::MessageEvent| match JsJobResult::from_js(event.data()) {
+                        Ok(result) => jobs.borrow_mut().complete(i, result),
+                        Err(e) => {
+                            crate::log!("{e}");
+                        }
+                    };
+                let name = format!("compute-worker-{i}");
+                Worker::spawn(WorkerKind::Compute, &name, i, onmessage)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            workers,
+            jobs,
+            next_id: Cell::new(0),
+        })
+    }
+
+    /// One less than the number of logical processors, leaving one to window, but at least one and
+    /// at most [`MAX_POOL_SIZE`].
+    pub fn default_size() -> usize {
+        let window = web_sys::window().unwrap();
+        let processors = window.navigator().hardware_concurrency() as usize;
+        processors.saturating_sub(1).clamp(1, MAX_POOL_SIZE)
+    }
+
+    /// Posts the task to the worker with the fewest jobs in flight.
+    pub fn submit(&self, task: JsTask) -> Job {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+
+        let slot = Rc::new(RefCell::new(Slot::default()));
+        let index = self.jobs.borrow_mut().add(id, Rc::clone(&slot));
+        let msg = JsJob { id, task };
+        self.workers[index].post_message(&msg.to_js()).unwrap();
+
+        Job { slot }
+    }
+}
+
+impl Drop for WorkerPool {
+    /// Fails jobs in flight, nothing will complete them after the workers are terminated.
+    fn drop(&mut self) {
+        let pending = std::mem::take(&mut self.jobs.borrow_mut().pending);
+        for (_, slot) in pending.into_values() {
+            slot.borrow_mut().fill(Err(JobError::Closed));
+        }
+    }
+}
+
+/// Jobs in flight.
+#[derive(Debug)]
+struct Jobs {
+    /// Worker index and result slot by job id.
+    pending: HashMap<u32, (usize, Rc<RefCell<Slot>>)>,
+    /// Number of jobs in flight on each worker.
+    loads: Vec<u32>,
+}
+
+impl Jobs {
+    /// Assigns the job to the least busy worker and returns its index.
+    fn add(&mut self, id: u32, slot: Rc<RefCell<Slot>>) -> usize {
+        let (index, load) = self
+            .loads
+            .iter_mut()
+            .enumerate()
+            .min_by_key(|(_, load)| **load)
+            .unwrap();
+        *load += 1;
+        self.pending.insert(id, (index, slot));
+        index
+    }
+
+    fn complete(&mut self, index: usize, result: JsJobResult) {
+        let Some((_, slot)) = self.pending.remove(&result.id) else {
+            crate::log!("unknown job id: {}", result.id);
+            return;
+        };
+        self.loads[index] -= 1;
+        slot.borrow_mut()
+            .fill(result.output.map_err(JobError::Failed));
+    }
+}
+
+#[derive(Debug, Default)]
+struct Slot {
+    output: Option<Result<JsOutput, JobError>>,
+    waker: Option<Waker>,
+}
+
+impl Slot {
+    fn fill(&mut self, output: Result<JsOutput, JobError>) {
+        self.output = Some(output);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A job submitted to [`WorkerPool`], which resolves to its output.
+#[derive(Debug)]
+pub struct Job {
+    slot: Rc<RefCell<Slot>>,
+}
+
+impl Future for Job {
+    type Output = Result<JsOutput, JobError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.borrow_mut();
+        match slot.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Error running a [`Job`].
+#[derive(Debug)]
+pub enum JobError {
+    /// The task failed on the worker.
+    Failed(String),
+    /// The pool was dropped before the job completed.
+    Closed,
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed(message) => write!(f, "job failed: {message}"),
+            Self::Closed => f.write_str("worker pool was closed"),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
@@ -6,6 +6,12 @@ export function createWorker(kind, name) {
         /* @vite-ignore */ name, // vite doesn't allow non static value here.
       });
       return main;
+    case 'compute':
+      const compute = new Worker(new URL('./computeWorker.js', import.meta.url), {
+        type: 'module',
+        /* @vite-ignore */ name,
+      });
+      return compute;
     default:
       console.log("unsurpported type of worker: ", kind);
       return undefined;
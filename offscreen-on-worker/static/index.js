@@ -4,3 +4,43 @@ await wbg_init();
 
 main();
 const app = new App();
+const status = document.querySelector("#status");
+const shaderError = document.querySelector("#shader_error");
+app.set_onreply((reply) => {
+  if (reply.type === "frameStats") {
+    status.textContent = `${reply.fps.toFixed(1)} fps, ${reply.maxFrameTime.toFixed(1)} ms max`;
+  } else if (reply.type === "shaderReloaded") {
+    shaderError.hidden = true;
+  } else if (reply.type === "shaderError") {
+    // The last good shader keeps drawing.
+    shaderError.textContent = reply.message;
+    shaderError.hidden = false;
+  } else {
+    console.log(reply);
+  }
+});
+app.add_canvas("#canvas0", 1);
+app.add_canvas("#canvas1", 2);
+app.generate_mesh(2, 16, 16).catch((e) => console.error(e));
+
+// Dev mode reloads the shader whenever it changes on the preview server.
+if (import.meta.env.MODE === "development") {
+  watchShader("/shaders/example.wgsl");
+}
+
+async function watchShader(url) {
+  let last = null;
+  for (;;) {
+    await new Promise((resolve) => setTimeout(resolve, 500));
+    const response = await fetch(url, { cache: "no-store" }).catch(() => null);
+    if (!response?.ok) {
+      // Not served.
+      return;
+    }
+    const source = await response.text();
+    if (source !== last) {
+      last = source;
+      app.set_shader(source);
+    }
+  }
+}
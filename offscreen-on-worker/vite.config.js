@@ -1,6 +1,33 @@
 import { defineConfig } from 'vite';
 import wasm from "vite-plugin-wasm";
 import topLevelAwait from "vite-plugin-top-level-await";
+import fs from 'node:fs';
+import path from 'node:path';
+
+const crossOriginIsolation = {
+  'Cross-Origin-Opener-Policy': 'same-origin',
+  'Cross-Origin-Embedder-Policy': 'require-corp',
+};
+
+// Serves shader sources under /shaders/, which the page polls in dev mode to reload them.
+const serveShaders = {
+  name: 'serve-shaders',
+  configurePreviewServer(server) {
+    server.middlewares.use('/shaders', (req, res) => {
+      const name = path.basename(req.url.split('?')[0]);
+      // npm scripts run in this directory.
+      fs.readFile(path.resolve('src', name), (err, data) => {
+        if (err || !name.endsWith('.wgsl')) {
+          res.statusCode = 404;
+          res.end();
+          return;
+        }
+        res.setHeader('Content-Type', 'text/plain');
+        res.end(data);
+      });
+    });
+  },
+};
 
 export default defineConfig({
   build: {
@@ -22,12 +49,16 @@ export default defineConfig({
     // Otherwise, we can restrict build.target to 'es2022', which allows top level await.
     wasm(),
     topLevelAwait(),
+    serveShaders,
   ],
+  // Cross-origin isolation lets window share input ring with the worker.
   server: {
     port: 8080,
+    headers: crossOriginIsolation,
   },
   preview: {
     port: 8080,
+    headers: crossOriginIsolation,
   },
 });
 
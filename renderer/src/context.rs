@@ -0,0 +1,247 @@
+use crate::{CapabilityReport, Error};
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+/// `wgpu::Adapter`, `wgpu::Device` and `wgpu::Queue`.
+///
+/// Cloning shares the same device, so renderers drawing onto different surfaces can be made from
+/// one context.
+///
+/// A GPU reset or a driver update loses the device. [`Context::watch`] notices that, then
+/// [`Context::recreate`] and [`Renderer::recover`] bring drawing back.
+///
+/// [`Renderer::recover`]: crate::Renderer::recover
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    /// Set by the hooks of [`Context::watch`]. Shared by clones.
+    lost: Arc<AtomicBool>,
+}
+
+/// What the device hooks of [`Context::watch`] report.
+#[derive(Debug)]
+pub enum DeviceEvent<'a> {
+    /// The device is lost, so nothing is drawn until the context is recreated.
+    /// Destroying the device on purpose isn't reported.
+    Lost {
+        reason: wgpu::DeviceLostReason,
+        message: String,
+    },
+    /// An error no error scope captured.
+    /// Out of memory and internal errors leave the context lost as well.
+    Error(&'a wgpu::Error),
+}
+
+impl Context {
+    /// Requests an adapter and then a device and a queue from it.
+    ///
+    /// * instance - A reference to a [`wgpu::Instance`].
+    /// * compatible_surface - Surface the adapter must be able to present to. WebGL2 requires
+    ///   this.
+    /// * limits - Limits the device is created with. e.g. [`wgpu::Limits::downlevel_webgl2_defaults`]
+    ///
+    /// Returns the error if no adapter is found or the adapter can't give a device, so that the
+    /// caller can try another backend.
+    pub async fn new(
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface<'_>>,
+        limits: wgpu::Limits,
+    ) -> Result<Self, Error> {
+        // Creates a `wgpu::Adapter`.
+        // Natively, `WGPU_ADAPTER_NAME` picks an adapter by name. e.g. `llvmpipe` on CI machines
+        // without a GPU.
+        let adapter =
+            wgpu::util::initialize_adapter_from_env_or_default(instance, compatible_surface)
+                .await?;
+
+        // Creates a `wgpu::Device` and a `wgpu::Queue`.
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                required_features: wgpu::Features::empty(),
+                required_limits: limits,
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Self {
+            adapter,
+            device,
+            queue,
+            lost: Arc::default(),
+        })
+    }
+
+    /// Requests a new adapter and a new device with the same limits, e.g. after the device is lost.
+    /// Hooks of [`Context::watch`] aren't carried over.
+    pub async fn recreate(
+        &self,
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface<'_>>,
+    ) -> Result<Self, Error> {
+        Self::new(instance, compatible_surface, self.device.limits()).await
+    }
+
+    /// Registers device-lost and uncaptured-error hooks, which call the listener and mark the
+    /// context lost when it can't be used anymore.
+    ///
+    /// Hooks may run in the middle of a frame, so check [`Context::is_lost`] once a frame rather
+    /// than recovering from the listener.
+    pub fn watch(&self, listener: impl Fn(DeviceEvent<'_>) + Send + Sync + 'static) {
+        let listener = Arc::new(listener);
+
+        let lost = Arc::clone(&self.lost);
+        let on_lost = Arc::clone(&listener);
+        self.device
+            .set_device_lost_callback(move |reason, message| {
+                // Destroyed on purpose, or dropped after recovery.
+                if reason == wgpu::DeviceLostReason::Destroyed {
+                    return;
+                }
+                lost.store(true, Ordering::Relaxed);
+                on_lost(DeviceEvent::Lost { reason, message });
+            });
+
+        let lost = Arc::clone(&self.lost);
+        self.device
+            .on_uncaptured_error(Arc::new(move |error: wgpu::Error| {
+                // Validation errors are bugs, which recreating the device doesn't fix.
+                if !matches!(error, wgpu::Error::Validation { .. }) {
+                    lost.store(true, Ordering::Relaxed);
+                }
+                listener(DeviceEvent::Error(&error));
+            }));
+    }
+
+    /// Returns true if the hooks of [`Context::watch`] have found the device unusable, or
+    /// [`Context::set_lost`] has been called.
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// Marks the context lost on losses wgpu doesn't report, e.g. `webglcontextlost` events.
+    pub fn set_lost(&self) {
+        self.lost.store(true, Ordering::Relaxed);
+    }
+
+    /// Collects what the adapter supports, and what the surface supports with it if given.
+    pub fn report(&self, surface: Option<&wgpu::Surface<'_>>) -> CapabilityReport {
+        CapabilityReport::new(&self.adapter, surface)
+    }
+}
+
+/// `wgpu::Surface` with its configuration.
+#[derive(Debug)]
+pub struct Surface {
+    pub surface: wgpu::Surface<'static>,
+    pub config: wgpu::SurfaceConfiguration,
+}
+
+impl Surface {
+    /// Configures the given surface with its preferred format and the given size.
+    ///
+    /// Returns [`Error::ConfigureSurface`] if the adapter can't present to the surface.
+    pub fn new(
+        surface: wgpu::Surface<'static>,
+        context: &Context,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        let surface_caps = surface.get_capabilities(&context.adapter);
+        let (format, present_mode, alpha_mode) = preferred(&surface_caps)?;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width,
+            height,
+            present_mode,
+            alpha_mode,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&context.device, &config);
+
+        Ok(Self { surface, config })
+    }
+
+    /// Configures the surface for a new context, e.g. after the device is lost.
+    /// Keeps the size, and the format and the modes if the new adapter still supports them.
+    pub fn recover(&mut self, context: &Context) -> Result<(), Error> {
+        let surface_caps = self.surface.get_capabilities(&context.adapter);
+        let (format, present_mode, alpha_mode) = preferred(&surface_caps)?;
+        if !surface_caps.formats.contains(&self.config.format) {
+            self.config.format = format;
+        }
+        if !surface_caps
+            .present_modes
+            .contains(&self.config.present_mode)
+        {
+            self.config.present_mode = present_mode;
+        }
+        if !surface_caps.alpha_modes.contains(&self.config.alpha_mode) {
+            self.config.alpha_mode = alpha_mode;
+        }
+        self.surface.configure(&context.device, &self.config);
+        Ok(())
+    }
+
+    /// Configures the surface again with the same configuration.
+    /// Needed when the surface becomes `Outdated` or `Lost`.
+    pub fn reconfigure(&self, device: &wgpu::Device) {
+        self.surface.configure(device, &self.config);
+    }
+
+    /// Reconfigures the surface if the size has changed.
+    /// Returns true if reconfigured.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) -> bool {
+        if width == self.config.width && height == self.config.height {
+            return false;
+        }
+        self.config.width = width;
+        self.config.height = height;
+        self.surface.configure(device, &self.config);
+        true
+    }
+
+    #[inline]
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.config.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.config.height
+    }
+}
+
+/// Returns the first format, present mode and alpha mode, which the surface prefers.
+/// Capabilities are empty if the adapter can't present to the surface.
+fn preferred(
+    caps: &wgpu::SurfaceCapabilities,
+) -> Result<
+    (
+        wgpu::TextureFormat,
+        wgpu::PresentMode,
+        wgpu::CompositeAlphaMode,
+    ),
+    Error,
+> {
+    match (
+        caps.formats.first(),
+        caps.present_modes.first(),
+        caps.alpha_modes.first(),
+    ) {
+        (Some(&format), Some(&present_mode), Some(&alpha_mode)) => {
+            Ok((format, present_mode, alpha_mode))
+        }
+        _ => Err(Error::ConfigureSurface),
+    }
+}
@@ -0,0 +1,111 @@
+use std::{fmt, io, path::PathBuf};
+
+/// Error creating a [`Context`](crate::Context) or a [`Renderer`](crate::Renderer), drawing a
+/// frame, or reading it back.
+#[derive(Debug)]
+pub enum Error {
+    /// No adapter of the instance's backends is available.
+    NoAdapter(wgpu::RequestAdapterError),
+    /// The adapter couldn't give a device with the requested limits.
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The surface couldn't be created from the window or the canvas.
+    CreateSurface(wgpu::CreateSurfaceError),
+    /// The surface can't be configured, because the adapter can't present to it.
+    ConfigureSurface,
+    /// The surface couldn't give a texture to draw onto.
+    Surface(wgpu::SurfaceError),
+    /// The device is lost. See [`Context::watch`](crate::Context::watch).
+    DeviceLost,
+    /// The shader source or the render pipeline made from it is invalid.
+    /// Holds the compiler's message.
+    Shader(String),
+    /// Waiting for the copy of the frame to finish failed.
+    Poll(wgpu::PollError),
+    /// The buffer the frame is copied to couldn't be mapped.
+    MapBuffer(wgpu::BufferAsyncError),
+    /// The file couldn't be written, e.g. a PNG of the frame.
+    Io(PathBuf, io::Error),
+}
+
+impl Error {
+    /// Returns true if the error may go away by itself, so the frame can just be skipped.
+    /// `Outdated` and `Lost` surfaces are reconfigured before this is returned.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Surface(
+                wgpu::SurfaceError::Timeout
+                    | wgpu::SurfaceError::Outdated
+                    | wgpu::SurfaceError::Lost
+            )
+        )
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAdapter(e) => write!(f, "no adapter: {e}"),
+            Self::RequestDevice(e) => write!(f, "no device: {e}"),
+            Self::CreateSurface(e) => write!(f, "failed to create a surface: {e}"),
+            Self::ConfigureSurface => f.write_str("the adapter can't present to the surface"),
+            Self::Surface(e) => write!(f, "surface error: {e}"),
+            Self::DeviceLost => f.write_str("the device is lost"),
+            Self::Shader(message) => write!(f, "invalid shader: {message}"),
+            Self::Poll(e) => write!(f, "failed to read the frame back: {e}"),
+            Self::MapBuffer(e) => write!(f, "failed to read the frame back: {e}"),
+            Self::Io(path, e) => write!(f, "{}: {e}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::NoAdapter(e) => Some(e),
+            Self::RequestDevice(e) => Some(e),
+            Self::CreateSurface(e) => Some(e),
+            Self::ConfigureSurface | Self::DeviceLost | Self::Shader(_) => None,
+            Self::Surface(e) => Some(e),
+            Self::Poll(e) => Some(e),
+            Self::MapBuffer(e) => Some(e),
+            Self::Io(_, e) => Some(e),
+        }
+    }
+}
+
+impl From<wgpu::RequestAdapterError> for Error {
+    fn from(value: wgpu::RequestAdapterError) -> Self {
+        Self::NoAdapter(value)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for Error {
+    fn from(value: wgpu::RequestDeviceError) -> Self {
+        Self::RequestDevice(value)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for Error {
+    fn from(value: wgpu::CreateSurfaceError) -> Self {
+        Self::CreateSurface(value)
+    }
+}
+
+impl From<wgpu::SurfaceError> for Error {
+    fn from(value: wgpu::SurfaceError) -> Self {
+        Self::Surface(value)
+    }
+}
+
+impl From<wgpu::PollError> for Error {
+    fn from(value: wgpu::PollError) -> Self {
+        Self::Poll(value)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for Error {
+    fn from(value: wgpu::BufferAsyncError) -> Self {
+        Self::MapBuffer(value)
+    }
+}
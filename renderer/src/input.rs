@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+
+/// Pointer, wheel and keyboard state, independent of where events come from.
+///
+/// Front-ends fill this in from their own events (DOM events, winit events or messages posted to
+/// a worker) converting positions to physical pixels, then [`Renderer::apply_input`] consumes the
+/// pointer and clicks once a frame. Camera controls read drags, the wheel and keys, such as
+/// `offscreen-on-worker`'s. Call [`Input::end_frame`] after that to clear what accumulates per
+/// frame.
+///
+/// [`Renderer::apply_input`]: crate::Renderer::apply_input
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    /// Pointer position in physical pixels. `None` until the pointer moves over the surface.
+    pub pointer: Option<[f32; 2]>,
+    /// Pressed buttons as `MouseEvent.buttons`. 1 is primary, 2 is secondary and 4 is auxiliary.
+    pub buttons: u16,
+    /// Position of the last click in this frame in physical pixels.
+    pub click: Option<[f32; 2]>,
+    /// Pointer movement while any button is pressed in this frame, in physical pixels.
+    pub drag: [f32; 2],
+    /// Wheel movement in this frame in pixels.
+    pub wheel: [f32; 2],
+    /// Held keys by physical key code such as "KeyA" or "ArrowLeft".
+    pub keys: HashSet<String>,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// * x, y - Pointer position in physical pixels.
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        if self.buttons != 0
+            && let Some([old_x, old_y]) = self.pointer
+        {
+            self.drag[0] += x - old_x;
+            self.drag[1] += y - old_y;
+        }
+        self.pointer = Some([x, y]);
+    }
+
+    /// * buttons - Bits of the pressed button as `MouseEvent.buttons`.
+    pub fn button_down(&mut self, buttons: u16) {
+        self.buttons |= buttons;
+    }
+
+    /// * buttons - Bits of the released button as `MouseEvent.buttons`.
+    pub fn button_up(&mut self, buttons: u16) {
+        self.buttons &= !buttons;
+    }
+
+    /// * x, y - Click position in physical pixels.
+    pub fn click(&mut self, x: f32, y: f32) {
+        self.pointer = Some([x, y]);
+        self.click = Some([x, y]);
+    }
+
+    /// * dx, dy - Wheel movement in pixels.
+    pub fn scroll(&mut self, dx: f32, dy: f32) {
+        self.wheel[0] += dx;
+        self.wheel[1] += dy;
+    }
+
+    pub fn key_down(&mut self, code: &str) {
+        self.keys.insert(code.to_owned());
+    }
+
+    pub fn key_up(&mut self, code: &str) {
+        self.keys.remove(code);
+    }
+
+    /// Returns true if the key of the given code is held.
+    pub fn is_pressed(&self, code: &str) -> bool {
+        self.keys.contains(code)
+    }
+
+    /// Clears clicks, drags and wheel movements accumulated in this frame.
+    /// Pointer position, buttons and keys are kept.
+    pub fn end_frame(&mut self) {
+        self.click = None;
+        self.drag = [0.0, 0.0];
+        self.wheel = [0.0, 0.0];
+    }
+}
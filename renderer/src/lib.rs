@@ -0,0 +1,50 @@
+//! Rendering code shared by the examples.
+//!
+//! All examples draw the same colored quad with the same uniform data. They only differ in where
+//! the surface comes from (a canvas, an offscreen canvas on a worker, ...) and which backend they
+//! run on. This crate owns the part they have in common, so each example is left with its own
+//! window, canvas or worker handling.
+//!
+//! Front-ends turn their own events into an [`Input`], which [`Renderer::apply_input`] writes to
+//! the uniform data, so pointer handling is the same wherever events come from.
+//!
+//! When the device is lost, [`Context::recreate`] and [`Renderer::recover`] rebuild everything from
+//! what the renderer keeps on the CPU side, so drawing goes on without a page reload.
+//!
+//! With `compose` feature, [`ShaderLibrary`] composes WGSL modules importing each other with
+//! naga_oil, and caches what it's composed. [`Permutations`] lists the `#ifdef` variants of a
+//! shader, which [`PermutationPipelines`] builds ahead of time to pick one while drawing.
+//!
+//! [`CapabilityReport`] describes what the adapter and the surface support, for bug reports.
+//!
+//! Frames can also be rendered into an [`OffscreenTarget`] and read back as a PNG, which needs
+//! neither a window nor a canvas.
+
+mod context;
+mod error;
+mod input;
+mod offscreen;
+#[cfg(feature = "compose")]
+mod permutation;
+mod quad;
+mod renderer;
+mod report;
+#[cfg(feature = "compose")]
+mod shader;
+mod uniform;
+
+pub use context::*;
+pub use error::*;
+pub use input::*;
+pub use offscreen::*;
+#[cfg(feature = "compose")]
+pub use permutation::*;
+pub use quad::*;
+pub use renderer::*;
+pub use report::*;
+#[cfg(feature = "compose")]
+pub use shader::*;
+pub use uniform::*;
+
+// Examples use wgpu through this crate so that all of them are on the same version.
+pub use wgpu;
@@ -0,0 +1,131 @@
+use crate::{Context, Error};
+
+/// Texture rendered into instead of a surface, so that frames can be produced and read back
+/// without a window or a canvas.
+#[derive(Debug)]
+pub struct OffscreenTarget {
+    pub texture: wgpu::Texture,
+}
+
+impl OffscreenTarget {
+    /// Format of the texture. Readback returns pixels in this format as is.
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        Self { texture }
+    }
+
+    /// Recreates the texture if the size has changed.
+    /// Returns true if recreated.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) -> bool {
+        if width == self.width() && height == self.height() {
+            return false;
+        }
+        *self = Self::new(device, width, height);
+        true
+    }
+
+    #[inline]
+    pub fn format(&self) -> wgpu::TextureFormat {
+        Self::FORMAT
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    /// Copies the texture to the CPU and returns tightly packed RGBA8 rows, top row first.
+    pub async fn read_pixels(&self, context: &Context) -> Result<Vec<u8>, Error> {
+        let (width, height) = (self.width(), self.height());
+
+        // Rows of a texture to buffer copy must be aligned to 256 bytes, so the buffer has padding
+        // at the end of each row.
+        let unpadded_bytes_per_row = width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        // Creates a `wgpu::Buffer` the texture is copied to.
+        let buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = context
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback command encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            self.texture.size(),
+        );
+        context.queue.submit(std::iter::once(encoder.finish()));
+
+        // Maps the buffer. Browsers map it on their own, but native backends need polling.
+        let (tx, rx) = futures_channel::oneshot::channel();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = tx.send(result);
+            });
+        context.device.poll(wgpu::PollType::wait_indefinitely())?;
+        // Dropped without being called if the device is lost.
+        rx.await.unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+        // Strips the padding.
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        {
+            let mapped = buffer.slice(..).get_mapped_range();
+            for row in mapped.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        buffer.unmap();
+
+        Ok(pixels)
+    }
+}
+
+/// Encodes RGBA8 pixels from [`OffscreenTarget::read_pixels`] as a PNG file.
+pub fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(pixels).unwrap();
+    writer.finish().unwrap();
+    png
+}
@@ -0,0 +1,188 @@
+use crate::{Error, Renderer, ShaderDefValue, ShaderDefs, ShaderLibrary};
+use std::{borrow::Cow, collections::HashSet, fmt, ops::RangeInclusive};
+
+/// A define a shader is permuted by, such as `UNIFORM` of `#ifdef UNIFORM`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Define {
+    pub name: &'static str,
+    pub kind: DefineKind,
+}
+
+/// Values a [`Define`] can take.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefineKind {
+    /// Used with `#ifdef`, `#ifndef` or `#if NAME == true`.
+    Bool,
+    /// Used with `#if NAME == 2` and such. Values out of the range are rejected.
+    Int(RangeInclusive<i32>),
+}
+
+impl Define {
+    pub const fn bool(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: DefineKind::Bool,
+        }
+    }
+
+    pub const fn int(name: &'static str, range: RangeInclusive<i32>) -> Self {
+        Self {
+            name,
+            kind: DefineKind::Int(range),
+        }
+    }
+
+    fn accepts(&self, value: ShaderDefValue) -> bool {
+        match (&self.kind, value) {
+            (DefineKind::Bool, ShaderDefValue::Bool(_)) => true,
+            (DefineKind::Int(range), ShaderDefValue::Int(v)) => range.contains(&v),
+            (DefineKind::Int(range), ShaderDefValue::UInt(v)) => {
+                i32::try_from(v).is_ok_and(|v| range.contains(&v))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Defines of one variant. Declared defines that aren't listed are left undefined.
+pub type Variant = &'static [(&'static str, ShaderDefValue)];
+
+/// Variants of a shader in a [`ShaderLibrary`], listed at compile time.
+///
+/// Only the listed combinations of defines are composed, not all possible ones. Declare them as
+/// constants, so that tests can validate every variant without drawing anything.
+///
+/// ```ignore
+/// const TOP: Permutations = Permutations {
+///     shader: "top.wgsl",
+///     defines: &[Define::bool("UNIFORM")],
+///     variants: &[&[], &[("UNIFORM", ShaderDefValue::Bool(true))]],
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct Permutations {
+    /// File path of the shader added by [`ShaderLibrary::add_shader`].
+    pub shader: &'static str,
+    pub defines: &'static [Define],
+    pub variants: &'static [Variant],
+}
+
+impl Permutations {
+    /// Returns defs of the variant at the index.
+    pub fn defs(&self, index: usize) -> ShaderDefs {
+        self.variants[index]
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value))
+            .collect()
+    }
+
+    /// Returns the index of the variant having exactly the defs.
+    pub fn find(&self, defs: &ShaderDefs) -> Option<usize> {
+        (0..self.variants.len()).find(|&i| &self.defs(i) == defs)
+    }
+
+    /// Checks that variants only use declared defines with values they accept, and don't repeat
+    /// themselves. Then composes every variant, so they're cached in the library.
+    pub fn validate(&self, library: &mut ShaderLibrary) -> Result<(), Error> {
+        let invalid = |index: usize, reason: String| {
+            let message = format!("{}, variant {index}: {reason}", self.shader);
+            Error::Shader(message)
+        };
+
+        let mut seen = HashSet::new();
+        for (index, variant) in self.variants.iter().enumerate() {
+            for &(name, value) in variant.iter() {
+                let Some(define) = self.defines.iter().find(|d| d.name == name) else {
+                    return Err(invalid(index, format!("{name} isn't declared")));
+                };
+                if !define.accepts(value) {
+                    return Err(invalid(index, format!("{name} can't be {value:?}")));
+                }
+            }
+            let defs = self.defs(index);
+            if defs.len() != variant.len() {
+                let reason = "a define is given more than once".to_owned();
+                return Err(invalid(index, reason));
+            }
+            if !seen.insert(defs) {
+                return Err(invalid(index, "same as an earlier variant".to_owned()));
+            }
+        }
+
+        for index in 0..self.variants.len() {
+            library
+                .compose(self.shader, &self.defs(index))
+                .map_err(|e| invalid(index, e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Permutations {
+    /// Lists variants one per line, e.g. `top.wgsl [UNIFORM=true]`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for index in 0..self.variants.len() {
+            let defs = self
+                .defs(index)
+                .into_iter()
+                .map(|(name, value)| match value {
+                    ShaderDefValue::Bool(v) => format!("{name}={v}"),
+                    ShaderDefValue::Int(v) => format!("{name}={v}"),
+                    ShaderDefValue::UInt(v) => format!("{name}={v}u"),
+                })
+                .collect::<Vec<_>>();
+            writeln!(f, "{} [{}]", self.shader, defs.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Render pipelines of every variant of [`Permutations`], built ahead of time so that switching
+/// variants while drawing costs nothing.
+#[derive(Debug)]
+pub struct PermutationPipelines {
+    pub permutations: Permutations,
+    /// Pipelines in the order of the variants.
+    pub pipelines: Vec<wgpu::RenderPipeline>,
+}
+
+impl PermutationPipelines {
+    /// Validates the permutations, then creates a render pipeline of each variant for the
+    /// renderer. Call this again after [`Renderer::recover`], as pipelines belong to the device.
+    ///
+    /// Like [`Renderer::create_pipeline`], the returned future doesn't borrow the renderer or the
+    /// library, and gives [`Error::Shader`] if any pipeline is invalid.
+    pub fn new(
+        renderer: &Renderer,
+        library: &mut ShaderLibrary,
+        permutations: &Permutations,
+    ) -> Result<impl Future<Output = Result<Self, Error>> + use<>, Error> {
+        permutations.validate(library)?;
+        let pending = (0..permutations.variants.len())
+            .map(|index| {
+                let defs = permutations.defs(index);
+                let module = library.compose(permutations.shader, &defs)?.clone();
+                let source = wgpu::ShaderSource::Naga(Cow::Owned(module));
+                Ok(renderer.create_pipeline(source))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let permutations = permutations.clone();
+
+        Ok(async move {
+            let mut pipelines = Vec::with_capacity(pending.len());
+            for pipeline in pending {
+                pipelines.push(pipeline.await?);
+            }
+            Ok(Self {
+                permutations,
+                pipelines,
+            })
+        })
+    }
+
+    /// Returns the pipeline of the variant having exactly the defs.
+    pub fn select(&self, defs: &ShaderDefs) -> Option<&wgpu::RenderPipeline> {
+        let index = self.permutations.find(defs)?;
+        Some(&self.pipelines[index])
+    }
+}
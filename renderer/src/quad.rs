@@ -0,0 +1,150 @@
+use std::mem;
+use wgpu::util::DeviceExt;
+
+/// Simple vertex format.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub pos: [f32; 3],
+    pub color: [f32; 3],
+}
+
+impl Vertex {
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    // pos
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    // color
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+/// Full screen quad.
+pub const QUAD_VERTICES: &[Vertex] = &[
+    Vertex {
+        pos: [-1.0, 1.0, 0.0],  // Top-left
+        color: [1.0, 0.0, 1.0], // Magenta
+    },
+    Vertex {
+        pos: [-1.0, -1.0, 0.0], // Bottom-left
+        color: [0.0, 0.0, 1.0], // Blue
+    },
+    Vertex {
+        pos: [1.0, 1.0, 0.0],   // Top-right
+        color: [1.0, 1.0, 0.0], // Yellow
+    },
+    Vertex {
+        pos: [1.0, -1.0, 0.0],  // Bottom-right
+        color: [0.0, 1.0, 0.0], // Green
+    },
+];
+
+pub const QUAD_INDICES: &[u32] = &[0, 1, 2, 2, 1, 3]; // CCW, quad
+
+/// Vertex and index buffers.
+///
+/// Vertices and indices are also kept on the CPU side, so the buffers can be made again on a new
+/// device.
+#[derive(Debug)]
+pub struct Mesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_indices: u32,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn new(device: &wgpu::Device, vertices: &[Vertex], indices: &[u32]) -> Self {
+        // Creates a `wgpu::Buffer` for the vertices.
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        // Creates a `wgpu::Buffer` for the indices.
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Index buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+            vertices: vertices.to_vec(),
+            indices: indices.to_vec(),
+        }
+    }
+
+    /// Creates the same buffers on the given device.
+    pub fn recreate(&self, device: &wgpu::Device) -> Self {
+        Self::new(device, &self.vertices, &self.indices)
+    }
+}
+
+/// Creates a render pipeline drawing [`Vertex`] triangles with `v_main` and `f_main` entry points.
+pub fn create_render_pipeline(
+    device: &wgpu::Device,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    shader_module: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render pipeline layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader_module,
+            entry_point: Some("v_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[Vertex::layout()],
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader_module,
+            entry_point: Some("f_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+        cache: None,
+    })
+}
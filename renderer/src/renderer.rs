@@ -0,0 +1,309 @@
+use crate::{
+    Context, Error, Input, Mesh, OffscreenTarget, Surface, Uniform, UniformData, Vertex,
+    create_render_pipeline, encode_png,
+};
+
+/// Parameters of [`Renderer::new`] and [`Renderer::new_offscreen`].
+#[derive(Debug)]
+pub struct RendererDescriptor<'a> {
+    pub vertices: &'a [Vertex],
+    pub indices: &'a [u32],
+    /// Shader module having `v_main` and `f_main` entry points.
+    pub shader_module: &'a wgpu::ShaderModule,
+    /// Surface width in physical pixels.
+    pub width: u32,
+    /// Surface height in physical pixels.
+    pub height: u32,
+    /// Device pixel ratio.
+    pub scale: f32,
+}
+
+/// Where [`Renderer`] draws to.
+#[derive(Debug)]
+pub enum Target {
+    Surface(Surface),
+    Offscreen(OffscreenTarget),
+}
+
+impl Target {
+    #[inline]
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            Self::Surface(surface) => surface.format(),
+            Self::Offscreen(target) => target.format(),
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        match self {
+            Self::Surface(surface) => surface.width(),
+            Self::Offscreen(target) => target.width(),
+        }
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        match self {
+            Self::Surface(surface) => surface.height(),
+            Self::Offscreen(target) => target.height(),
+        }
+    }
+}
+
+/// Draws a mesh onto a surface or an offscreen texture with [`UniformData`] bound at group 0.
+#[derive(Debug)]
+pub struct Renderer {
+    pub context: Context,
+    pub target: Target,
+    pub mesh: Mesh,
+    pub uniform: Uniform,
+    pub render_pipeline: wgpu::RenderPipeline,
+}
+
+impl Renderer {
+    /// Configures the surface and creates buffers and a render pipeline on the given context.
+    /// Returns [`Error::ConfigureSurface`] if the adapter can't present to the surface.
+    pub fn new(
+        context: Context,
+        surface: wgpu::Surface<'static>,
+        desc: &RendererDescriptor<'_>,
+    ) -> Result<Self, Error> {
+        // Configures the surface.
+        let surface = Surface::new(surface, &context, desc.width, desc.height)?;
+        Ok(Self::with_target(context, Target::Surface(surface), desc))
+    }
+
+    /// Creates an offscreen texture and buffers and a render pipeline on the given context.
+    /// Use [`Renderer::read_png`] to get rendered frames.
+    pub fn new_offscreen(context: Context, desc: &RendererDescriptor<'_>) -> Self {
+        let target = OffscreenTarget::new(&context.device, desc.width, desc.height);
+        Self::with_target(context, Target::Offscreen(target), desc)
+    }
+
+    fn with_target(context: Context, target: Target, desc: &RendererDescriptor<'_>) -> Self {
+        let device = &context.device;
+
+        // Creates `wgpu::Buffer`s for the vertices and the indices.
+        let mesh = Mesh::new(device, desc.vertices, desc.indices);
+
+        // Creates a `wgpu::Buffer` for the uniform data.
+        let uniform = Uniform::new(
+            device,
+            UniformData::new(desc.width, desc.height, desc.scale),
+        );
+
+        // Creates a `wgpu::RenderPipeline`.
+        let render_pipeline = create_render_pipeline(
+            device,
+            &[&uniform.bind_group_layout],
+            desc.shader_module,
+            target.format(),
+        );
+
+        Self {
+            context,
+            target,
+            mesh,
+            uniform,
+            render_pipeline,
+        }
+    }
+
+    /// Draws a frame onto the target.
+    /// Skips the frame on [transient](Error::is_transient) surface errors or a lost device, and
+    /// panics on the others.
+    ///
+    /// * time - Milliseconds as `requestAnimationFrame()` gives.
+    pub fn render(&mut self, time: f32) {
+        if let Err(e) = self.try_render(time)
+            && !e.is_transient()
+            && !matches!(e, Error::DeviceLost)
+        {
+            panic!("{e}");
+        }
+    }
+
+    /// Same as [`Renderer::render`], but returns the error if the surface can't give a texture,
+    /// skipping the frame.
+    ///
+    /// `Outdated` or `Lost` surface is reconfigured and asked once more before the error is
+    /// returned. Nothing is drawn once the context is lost, until [`Renderer::recover`].
+    pub fn try_render(&mut self, time: f32) -> Result<(), Error> {
+        if self.context.is_lost() {
+            return Err(Error::DeviceLost);
+        }
+
+        // Write uniform data to its buffer
+        self.uniform.data.time = time * 0.001;
+        self.uniform.write(&self.context.queue);
+
+        let mut encoder =
+            self.context
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Render command encoder"),
+                });
+        match &self.target {
+            Target::Surface(surface) => {
+                let surface_texture = match surface.surface.get_current_texture() {
+                    Ok(surface_texture) => surface_texture,
+                    Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                        surface.reconfigure(&self.context.device);
+                        surface.surface.get_current_texture()?
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                let texture_view = surface_texture.texture.create_view(&Default::default());
+                self.draw(&mut encoder, &texture_view);
+                self.context.queue.submit(std::iter::once(encoder.finish()));
+                surface_texture.present();
+            }
+            Target::Offscreen(target) => {
+                let texture_view = target.texture.create_view(&Default::default());
+                self.draw(&mut encoder, &texture_view);
+                self.context.queue.submit(std::iter::once(encoder.finish()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves onto a new context, e.g. after the device is lost, creating the target, buffers, the
+    /// bind group and the render pipeline again from what's kept on the CPU side.
+    ///
+    /// * shader_module - Module created on the new device from the same source as the one given
+    ///   to [`Renderer::new`].
+    pub fn recover(
+        &mut self,
+        context: Context,
+        shader_module: &wgpu::ShaderModule,
+    ) -> Result<(), Error> {
+        let device = &context.device;
+        match &mut self.target {
+            Target::Surface(surface) => surface.recover(&context)?,
+            Target::Offscreen(target) => {
+                *target = OffscreenTarget::new(device, target.width(), target.height());
+            }
+        }
+        self.mesh = self.mesh.recreate(device);
+        self.uniform = Uniform::new(device, self.uniform.data);
+        self.render_pipeline = create_render_pipeline(
+            device,
+            &[&self.uniform.bind_group_layout],
+            shader_module,
+            self.target.format(),
+        );
+        self.context = context;
+        Ok(())
+    }
+
+    /// Creates a render pipeline from another shader source, for hot reloading.
+    ///
+    /// The returned future gives [`Error::Shader`] if the source or the pipeline is invalid, and
+    /// doesn't borrow the renderer. Put the pipeline in [`Renderer::render_pipeline`] once it
+    /// resolves, so the current one keeps drawing on errors.
+    pub fn create_pipeline(
+        &self,
+        source: wgpu::ShaderSource<'_>,
+    ) -> impl Future<Output = Result<wgpu::RenderPipeline, Error>> + use<> {
+        let device = &self.context.device;
+
+        // Captures errors instead of reporting them as uncaptured ones.
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Reloaded shader module"),
+            source,
+        });
+        let render_pipeline = create_render_pipeline(
+            device,
+            &[&self.uniform.bind_group_layout],
+            &shader_module,
+            self.target.format(),
+        );
+        let error = device.pop_error_scope();
+
+        async move {
+            match error.await {
+                None => Ok(render_pipeline),
+                Some(wgpu::Error::Validation { description, .. }) => {
+                    Err(Error::Shader(description))
+                }
+                Some(other) => Err(Error::Shader(other.to_string())),
+            }
+        }
+    }
+
+    /// Reads the last rendered frame back and encodes it as a PNG file.
+    /// Returns `None` if the renderer draws onto a surface.
+    pub async fn read_png(&self) -> Result<Option<Vec<u8>>, Error> {
+        let Target::Offscreen(target) = &self.target else {
+            return Ok(None);
+        };
+        let pixels = target.read_pixels(&self.context).await?;
+        Ok(Some(encode_png(target.width(), target.height(), &pixels)))
+    }
+
+    /// Records a render pass drawing the mesh onto the given view.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.0,
+                        g: 0.0,
+                        b: 0.0,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, self.mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.set_bind_group(0, &self.uniform.bind_group, &[]);
+        render_pass.draw_indexed(0..self.mesh.num_indices, 0, 0..1);
+    }
+
+    /// Reconfigures the surface or recreates the offscreen texture and updates the resolution if
+    /// the size has changed. Returns true if resized.
+    pub fn resize(&mut self, width: u32, height: u32) -> bool {
+        let device = &self.context.device;
+        let resized = match &mut self.target {
+            Target::Surface(surface) => surface.resize(device, width, height),
+            Target::Offscreen(target) => target.resize(device, width, height),
+        };
+        if resized {
+            self.uniform.data.resolution = [width as f32, height as f32];
+        }
+        resized
+    }
+
+    /// Writes pointer position and the last click to the uniform data.
+    /// Called once a frame before [`Renderer::render`].
+    pub fn apply_input(&mut self, input: &Input) {
+        if let Some([x, y]) = input.pointer {
+            self.mouse_move(x, y);
+        }
+        if let Some([x, y]) = input.click {
+            self.mouse_click(x, y);
+        }
+    }
+
+    /// * x, y - Mouse position in physical pixels.
+    pub fn mouse_move(&mut self, x: f32, y: f32) {
+        self.uniform.data.mouse_move = [x, y];
+    }
+
+    /// * x, y - Mouse position in physical pixels.
+    pub fn mouse_click(&mut self, x: f32, y: f32) {
+        self.uniform.data.mouse_click = [x, y];
+    }
+}
@@ -0,0 +1,73 @@
+use serde::Serialize;
+use std::fmt;
+
+/// What the adapter supports, and what the surface supports if given.
+///
+/// Attach this to bug reports, so they contain the exact environment. It's printable as text with
+/// `Display`, and serializable into JSON or a JS object with serde.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapabilityReport {
+    pub adapter: wgpu::AdapterInfo,
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    pub downlevel: wgpu::DownlevelCapabilities,
+    /// `None` if no surface was given.
+    pub surface: Option<SurfaceReport>,
+}
+
+/// What a surface supports with an adapter.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SurfaceReport {
+    pub formats: Vec<wgpu::TextureFormat>,
+    pub present_modes: Vec<wgpu::PresentMode>,
+    pub alpha_modes: Vec<wgpu::CompositeAlphaMode>,
+}
+
+impl CapabilityReport {
+    /// Collects capabilities of the adapter, and of the surface with the adapter.
+    pub fn new(adapter: &wgpu::Adapter, surface: Option<&wgpu::Surface<'_>>) -> Self {
+        let surface = surface.map(|surface| {
+            let caps = surface.get_capabilities(adapter);
+            SurfaceReport {
+                formats: caps.formats,
+                present_modes: caps.present_modes,
+                alpha_modes: caps.alpha_modes,
+            }
+        });
+        Self {
+            adapter: adapter.get_info(),
+            features: adapter.features(),
+            limits: adapter.limits(),
+            downlevel: adapter.get_downlevel_capabilities(),
+            surface,
+        }
+    }
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = &self.adapter;
+        writeln!(f, "Adapter: {} ({:?})", info.name, info.device_type)?;
+        writeln!(f, "Backend: {:?}", info.backend)?;
+        writeln!(
+            f,
+            "Vendor: {:#06x}, Device: {:#06x}",
+            info.vendor, info.device
+        )?;
+        writeln!(f, "Driver: {} {}", info.driver, info.driver_info)?;
+        writeln!(f, "Features: {}", self.features)?;
+        writeln!(f, "Downlevel flags: {:?}", self.downlevel.flags)?;
+        writeln!(f, "Shader model: {:?}", self.downlevel.shader_model)?;
+        writeln!(f, "Limits: {:#?}", self.limits)?;
+        match &self.surface {
+            Some(surface) => {
+                writeln!(f, "Surface formats: {:?}", surface.formats)?;
+                writeln!(f, "Surface present modes: {:?}", surface.present_modes)?;
+                write!(f, "Surface alpha modes: {:?}", surface.alpha_modes)
+            }
+            None => write!(f, "Surface: none"),
+        }
+    }
+}
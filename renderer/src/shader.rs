@@ -0,0 +1,146 @@
+use crate::Error;
+use naga_oil::compose::{ComposableModuleDescriptor, Composer, NagaModuleDescriptor};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
+use wgpu::naga;
+
+pub use naga_oil::compose::ShaderDefValue;
+
+/// Shader defs composing a shader with, such as `UNIFORM` of `#ifdef UNIFORM`.
+/// Sorted, so that the same set of defs always makes the same cache key.
+pub type ShaderDefs = BTreeMap<String, ShaderDefValue>;
+
+/// WGSL modules composed with naga_oil.
+///
+/// Composable modules, which are imported with `#import`, are registered once, then any number of
+/// shaders importing them can be composed. Composed modules are cached by the shader's file path
+/// and its [`ShaderDefs`], so pipelines sharing a shader don't compose it again.
+///
+/// ```ignore
+/// let mut library = ShaderLibrary::new();
+/// library.add_module("uniform.wgsl", include_str!("uniform.wgsl"))?;
+/// library.add_shader("top.wgsl", include_str!("top.wgsl"));
+/// let defs = [("UNIFORM".to_owned(), ShaderDefValue::Bool(true))].into();
+/// let shader_module = library.create_shader_module(&device, "top.wgsl", &defs)?;
+/// ```
+#[derive(Debug, Default)]
+pub struct ShaderLibrary {
+    composer: Composer,
+    /// Composable modules by file path, in the order they were added.
+    modules: Vec<(String, String)>,
+    /// Shaders by file path.
+    shaders: HashMap<String, String>,
+    /// Composed shaders by file path and shader defs.
+    cache: HashMap<(String, ShaderDefs), naga::Module>,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a composable module, which must have `#define_import_path`.
+    /// Modules it imports must be added before.
+    ///
+    /// Adding a file path again replaces the module, and composes cached shaders again. The library
+    /// stays as it was if any of them doesn't compose with the new source.
+    pub fn add_module(&mut self, file_path: &str, source: &str) -> Result<(), Error> {
+        let Some(index) = self.modules.iter().position(|(path, _)| path == file_path) else {
+            add_composable_module(&mut self.composer, file_path, source)?;
+            self.modules.push((file_path.to_owned(), source.to_owned()));
+            return Ok(());
+        };
+
+        // Modules importing the replaced one may be affected, so all of them are added again.
+        let mut modules = self.modules.clone();
+        modules[index].1 = source.to_owned();
+        let mut composer = Composer::default();
+        for (path, source) in &modules {
+            add_composable_module(&mut composer, path, source)?;
+        }
+        // naga_oil parses modules when they're imported, so errors show up here.
+        let mut cache = HashMap::with_capacity(self.cache.len());
+        for (path, defs) in self.cache.keys() {
+            let module = make_naga_module(&mut composer, path, &self.shaders[path], defs)?;
+            cache.insert((path.clone(), defs.clone()), module);
+        }
+        self.composer = composer;
+        self.modules = modules;
+        self.cache = cache;
+        Ok(())
+    }
+
+    /// Adds a shader, which is composed into a module with entry points.
+    /// Adding a file path again replaces the shader, and drops its composed modules.
+    pub fn add_shader(&mut self, file_path: &str, source: &str) {
+        self.shaders.insert(file_path.to_owned(), source.to_owned());
+        self.cache.retain(|(path, _), _| path != file_path);
+    }
+
+    /// Composes the shader with the defs, or returns the cached module if it's been composed.
+    pub fn compose(&mut self, file_path: &str, defs: &ShaderDefs) -> Result<&naga::Module, Error> {
+        let key = (file_path.to_owned(), defs.clone());
+        if !self.cache.contains_key(&key) {
+            let source = self
+                .shaders
+                .get(file_path)
+                .ok_or_else(|| Error::Shader(format!("no shader is added as {file_path}")))?;
+            let module = make_naga_module(&mut self.composer, file_path, source, defs)?;
+            self.cache.insert(key.clone(), module);
+        }
+        Ok(&self.cache[&key])
+    }
+
+    /// Composes the shader with the defs, then creates a [`wgpu::ShaderModule`] from it.
+    pub fn create_shader_module(
+        &mut self,
+        device: &wgpu::Device,
+        file_path: &str,
+        defs: &ShaderDefs,
+    ) -> Result<wgpu::ShaderModule, Error> {
+        let module = self.compose(file_path, defs)?.clone();
+        Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(file_path),
+            source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+        }))
+    }
+
+    /// Returns true if the shader has been composed with the defs.
+    pub fn is_cached(&self, file_path: &str, defs: &ShaderDefs) -> bool {
+        self.cache
+            .contains_key(&(file_path.to_owned(), defs.clone()))
+    }
+}
+
+fn add_composable_module(
+    composer: &mut Composer,
+    file_path: &str,
+    source: &str,
+) -> Result<(), Error> {
+    composer
+        .add_composable_module(ComposableModuleDescriptor {
+            source,
+            file_path,
+            ..Default::default()
+        })
+        .map(|_| ())
+        .map_err(|e| Error::Shader(e.emit_to_string(composer)))
+}
+
+fn make_naga_module(
+    composer: &mut Composer,
+    file_path: &str,
+    source: &str,
+    defs: &ShaderDefs,
+) -> Result<naga::Module, Error> {
+    composer
+        .make_naga_module(NagaModuleDescriptor {
+            source,
+            file_path,
+            shader_defs: defs.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+            ..Default::default()
+        })
+        .map_err(|e| Error::Shader(e.emit_to_string(composer)))
+}
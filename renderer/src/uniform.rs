@@ -0,0 +1,87 @@
+use wgpu::util::DeviceExt;
+
+/// Uniform data every example shader reads.
+/// Keep this in sync with `struct UniformData` in the WGSL sources. They may leave out trailing
+/// fields they don't read.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Default)]
+pub struct UniformData {
+    pub mouse_move: [f32; 2],
+    pub mouse_click: [f32; 2],
+    pub resolution: [f32; 2],
+    pub scale: f32,
+    pub time: f32,
+    /// Transform of vertex positions as columns. Identity unless the example moves the view.
+    pub view: [[f32; 4]; 4],
+}
+
+impl UniformData {
+    /// Mouse positions start far away from the screen so that nothing is highlighted.
+    pub fn new(width: u32, height: u32, scale: f32) -> Self {
+        Self {
+            mouse_move: [f32::MIN, f32::MIN],
+            mouse_click: [f32::MIN, f32::MIN],
+            resolution: [width as f32, height as f32],
+            scale,
+            time: 0.0,
+            view: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+}
+
+/// [`UniformData`] with its buffer and bind group.
+#[derive(Debug)]
+pub struct Uniform {
+    pub data: UniformData,
+    pub buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Uniform {
+    pub fn new(device: &wgpu::Device, data: UniformData) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Uniform buffer"),
+            contents: bytemuck::cast_slice(&[data][..]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Uniform bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Uniform bind group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            data,
+            buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// Writes current data to the buffer.
+    pub fn write(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.data][..]));
+    }
+}
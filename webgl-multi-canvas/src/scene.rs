@@ -0,0 +1,61 @@
+//! Meshes drawn on the canvases. Kept apart from the web code so that they can be rendered
+//! natively as well. See `golden` at the repository root.
+
+use renderer::Vertex;
+
+/// Returns a mesh for each canvas, which has one, two and three squares respectively.
+pub fn meshes() -> [(Vec<Vertex>, Vec<u32>); 3] {
+    // First canvas has single square.
+    let vertices0 = vec![
+        // Top-left (magenta)
+        Vertex {
+            pos: [-0.1, 1.0, 0.0],
+            color: [1.0, 0.0, 1.0],
+        },
+        // Bottom-left (blue)
+        Vertex {
+            pos: [-0.1, -1.0, 0.0],
+            color: [0.0, 0.0, 1.0],
+        },
+        // Top-right (yello)
+        Vertex {
+            pos: [0.1, 1.0, 0.0],
+            color: [1.0, 1.0, 0.0],
+        },
+        // Bottom-right (green)
+        Vertex {
+            pos: [0.1, -1.0, 0.0],
+            color: [0.0, 1.0, 0.0],
+        },
+    ];
+    let indices0 = vec![0, 1, 2, 2, 1, 3];
+
+    // Second canvas had two squares.
+    let mut vertices1 = vertices0.clone();
+    vertices1.extend(vertices0.clone());
+    for vertex in vertices1.iter_mut().take(4) {
+        vertex.pos[0] -= 0.2;
+    }
+    for vertex in vertices1.iter_mut().skip(4).take(4) {
+        vertex.pos[0] += 0.2;
+    }
+    let indices1 = vec![0, 1, 2, 2, 1, 3, 4, 5, 6, 6, 5, 7];
+
+    // Third canvas has three squares.
+    let mut vertices2 = vertices0.clone();
+    vertices2.extend(vertices0.clone());
+    vertices2.extend(vertices0.clone());
+    for vertex in vertices2.iter_mut().take(4) {
+        vertex.pos[0] -= 0.4;
+    }
+    for vertex in vertices2.iter_mut().skip(8).take(4) {
+        vertex.pos[0] += 0.4;
+    }
+    let indices2 = vec![0, 1, 2, 2, 1, 3, 4, 5, 6, 6, 5, 7, 8, 9, 10, 10, 9, 11];
+
+    [
+        (vertices0, indices0),
+        (vertices1, indices1),
+        (vertices2, indices2),
+    ]
+}
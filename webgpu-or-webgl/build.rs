@@ -0,0 +1,10 @@
+//! Fails the build if a shader in `src` is invalid for the backends this example runs on.
+
+use wgsl_check::{Check, Target};
+
+fn main() {
+    Check::new("src")
+        .target(Target::WebGpu)
+        .target(Target::WebGl2)
+        .build();
+}
@@ -1,12 +1,14 @@
-//! Please look for `cfg!(feature = webgl)`.
-
-use std::mem;
-use wasm_bindgen::prelude::*;
-use wgpu::util::DeviceExt;
-use winit::{
-    dpi::PhysicalSize, event_loop::EventLoop, platform::web::WindowBuilderExtWebSys,
-    window::WindowBuilder,
+//! One build runs on WebGPU, or on WebGL2 where WebGPU isn't available.
+//! Please look for [`State::create_context`].
+//!
+//! Either way, a lost device is recreated without a page reload. See [`State::recover`].
+
+use renderer::{
+    wgpu, Context, DeviceEvent, Error, Input, Renderer, RendererDescriptor, Target, QUAD_INDICES,
+    QUAD_VERTICES,
 };
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
 
 macro_rules! log {
     ($($t:tt)*) => {
@@ -14,159 +16,66 @@ macro_rules! log {
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    pos: [f32; 3],
-    color: [f32; 3],
-}
-
-impl Vertex {
-    fn layout() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    // pos
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    // color
-                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-            ],
-        }
-    }
-}
-
-const VERTICES: &[Vertex] = &[
-    Vertex {
-        pos: [-1.0, 1.0, 0.0],  // Top-left
-        color: [1.0, 0.0, 1.0], // Magenta
-    },
-    Vertex {
-        pos: [-1.0, -1.0, 0.0], // Bottom-left
-        color: [0.0, 0.0, 1.0], // Blue
-    },
-    Vertex {
-        pos: [1.0, 1.0, 0.0],   // Top-right
-        color: [1.0, 1.0, 0.0], // Yellow
-    },
-    Vertex {
-        pos: [1.0, -1.0, 0.0],  // Bottom-right
-        color: [0.0, 1.0, 0.0], // Green
-    },
-];
-
-const INDICES: &[u32] = &[0, 1, 2, 2, 1, 3]; // CCW, quad
-
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Default)]
-struct UniformData {
-    mouse_move: [f32; 2],
-    mouse_click: [f32; 2],
-    resolution: [f32; 2],
-    time: f32,
-    _padding: f32,
-}
-
 #[derive(Debug)]
 struct State {
     window: web_sys::Window,
+    /// Kept to recreate the context after the device is lost.
+    instance: wgpu::Instance,
     canvas: web_sys::HtmlCanvasElement,
-    winit_window: winit::window::Window,
-    surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    surface_config: wgpu::SurfaceConfiguration,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    index_num: u32,
-    uniform_data: UniformData,
-    uniform_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
-    render_pipeline: wgpu::RenderPipeline,
+    renderer: Renderer,
+    /// Backend chosen at runtime, `BrowserWebGpu` or `Gl`.
+    backend: wgpu::Backend,
+    /// Whether the canvas got `webglcontextrestored` after the WebGL2 context was lost.
+    restored: bool,
+    input: Input,
     animation_cb: Closure<dyn FnMut(f32)>,
 }
 
-static mut STATE: Option<State> = None;
+thread_local! {
+    /// Taken out while recovering, so that nothing else touches the state across the awaits.
+    static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
+}
 
 impl State {
-    async fn new() -> Self {
+    async fn new() -> Result<Self, Error> {
         // window
         let window = get_window();
         // canvas
         let canvas = State::init_canvas(&window).expect_throw("Failed to get canvas");
-        // winit window
-        let winit_window = State::create_window(&canvas).expect_throw("Failed to create window");
-        // wgpu instance
-        let instance = State::create_instance();
-        // wgpu surface
-        let surface = State::create_surface(&instance, &winit_window)
-            .expect_throw("Failed to create surface");
-        // wgpu adapter
-        let adapter = State::create_adapter(&instance, &surface)
-            .await
-            .expect_throw("Failed to create adapter");
-        // wgpu device and queue
-        let (device, queue) = State::create_device_and_queue(&adapter)
-            .await
-            .expect_throw("Failed to create device");
-        // wgpu surface configuration
-        let surface_config = State::create_surface_configuration(
-            &surface,
-            &adapter,
-            &device,
-            canvas.width(),
-            canvas.height(),
-        );
-        // wgpu vertex buffer
-        let vertex_buffer = State::create_vertex_buffer(&device, bytemuck::cast_slice(VERTICES));
-        // wgpu index buffer
-        let index_buffer = State::create_index_buffer(&device, bytemuck::cast_slice(INDICES));
-        // wgpu uniform buffer
-        let uniform_data = UniformData {
-            resolution: [canvas.width() as f32, canvas.height() as f32],
-            mouse_move: [std::f32::MIN, std::f32::MIN],
-            mouse_click: [std::f32::MIN, std::f32::MIN],
-            ..Default::default()
-        };
-        let (uniform_buffer, uniform_layout, uniform_bind_group) =
-            State::create_uniform_buffer(&device, bytemuck::cast_slice(&[uniform_data][..]));
+        // wgpu surface, adapter, device and queue of WebGPU or WebGL2
+        let (instance, surface, context) = State::create_context(&canvas).await?;
+        let backend = context.adapter.get_info().backend;
+        log!("Backend: {:?}", backend);
+        log!("{}", context.report(Some(&surface)));
+        State::watch_device(&context);
         // wgpu shader module
-        let shader_module = State::create_shader_module(&device);
-        // wgpu render pipeline
-        let render_pipeline = State::create_render_pipeline(
-            &device,
-            &[&uniform_layout],
-            &shader_module,
-            &surface_config,
-        );
+        let shader_module = State::create_shader_module(&context.device);
+        // wgpu surface configuration, buffers and render pipeline
+        let renderer = Renderer::new(
+            context,
+            surface,
+            &RendererDescriptor {
+                vertices: QUAD_VERTICES,
+                indices: QUAD_INDICES,
+                shader_module: &shader_module,
+                width: canvas.width(),
+                height: canvas.height(),
+                scale: 1.0, // Canvas size isn't scaled in this example.
+            },
+        )?;
         // animation_loop
         let animation_cb = State::create_animation_loop();
 
-        Self {
+        Ok(Self {
             window,
+            instance,
             canvas,
-            winit_window,
-            surface,
-            device,
-            queue,
-            surface_config,
-            vertex_buffer,
-            index_buffer,
-            index_num: INDICES.len() as u32,
-            uniform_data,
-            uniform_buffer,
-            uniform_bind_group,
-            render_pipeline,
+            renderer,
+            backend,
+            restored: false,
+            input: Input::new(),
             animation_cb,
-        }
+        })
     }
 
     fn init_canvas(window: &web_sys::Window) -> Option<web_sys::HtmlCanvasElement> {
@@ -179,145 +88,45 @@ impl State {
         Some(canvas)
     }
 
-    fn create_window(canvas: &web_sys::HtmlCanvasElement) -> Option<winit::window::Window> {
-        // winit's window writes its physical and logical sizes in the HTML element
-        // as attributes and inner style respectively
-        // So, we should put "!important" in CSS to overwrite the inner style
-        let window = WindowBuilder::new()
-            .with_inner_size(PhysicalSize::new(
-                canvas.client_width(),
-                canvas.client_height(),
-            ))
-            .with_canvas(Some(canvas.clone()))
-            .build(&EventLoop::new())
-            .ok()?;
-        log!(
-            "Window size: ({}, {})",
-            window.inner_size().width,
-            window.inner_size().height
-        );
-        Some(window)
+    /// Tries WebGPU first, and then WebGL2 if no WebGPU adapter is found.
+    async fn create_context(
+        canvas: &web_sys::HtmlCanvasElement,
+    ) -> Result<(wgpu::Instance, wgpu::Surface<'static>, Context), Error> {
+        // WebGPU finds an adapter without a surface. The canvas must not be taken before that,
+        // because a canvas can't have a WebGL2 context once it has a WebGPU context.
+        // The instance can't find any adapters if the browser doesn't have `navigator.gpu`.
+        let instance = State::create_instance(wgpu::Backends::BROWSER_WEBGPU);
+        match Context::new(&instance, None, wgpu::Limits::default()).await {
+            Ok(context) => {
+                let surface = State::create_surface(&instance, canvas)?;
+                return Ok((instance, surface, context));
+            }
+            Err(e) => {
+                log!("WebGPU is not available, falls back to WebGL2: {e}");
+            }
+        }
+
+        // WebGL2 needs a surface to find an adapter.
+        let instance = State::create_instance(wgpu::Backends::GL);
+        let surface = State::create_surface(&instance, canvas)?;
+        // WebGL will get error with the default limits.
+        let limits = wgpu::Limits::downlevel_webgl2_defaults();
+        let context = Context::new(&instance, Some(&surface), limits).await?;
+        Ok((instance, surface, context))
     }
 
-    fn create_instance() -> wgpu::Instance {
-        wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: if cfg!(feature = "webgl") {
-                wgpu::Backends::GL
-            } else {
-                wgpu::Backends::BROWSER_WEBGPU
-            },
+    fn create_instance(backends: wgpu::Backends) -> wgpu::Instance {
+        wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends,
             ..Default::default()
         })
     }
 
     fn create_surface(
         instance: &wgpu::Instance,
-        winit_window: &winit::window::Window,
-    ) -> Option<wgpu::Surface> {
-        unsafe { instance.create_surface(winit_window).ok() }
-    }
-
-    async fn create_adapter(
-        instance: &wgpu::Instance,
-        surface: &wgpu::Surface,
-    ) -> Option<wgpu::Adapter> {
-        instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                compatible_surface: Some(surface),
-                force_fallback_adapter: false,
-                ..Default::default()
-            })
-            .await
-    }
-
-    async fn create_device_and_queue(
-        adapter: &wgpu::Adapter,
-    ) -> Option<(wgpu::Device, wgpu::Queue)> {
-        adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    limits: if cfg!(feature = "webgl") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default() // WebGL will get error with this condition
-                    },
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .ok()
-    }
-
-    fn create_surface_configuration(
-        surface: &wgpu::Surface,
-        adapter: &wgpu::Adapter,
-        device: &wgpu::Device,
-        width: u32,
-        height: u32,
-    ) -> wgpu::SurfaceConfiguration {
-        let surface_caps = surface.get_capabilities(adapter);
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_caps.formats[0],
-            width,
-            height,
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-        };
-        surface.configure(device, &surface_config);
-        surface_config
-    }
-
-    fn create_vertex_buffer(device: &wgpu::Device, contents: &[u8]) -> wgpu::Buffer {
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex buffer"),
-            contents,
-            usage: wgpu::BufferUsages::VERTEX,
-        })
-    }
-
-    fn create_index_buffer(device: &wgpu::Device, contents: &[u8]) -> wgpu::Buffer {
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index buffer"),
-            contents,
-            usage: wgpu::BufferUsages::INDEX,
-        })
-    }
-
-    fn create_uniform_buffer(
-        device: &wgpu::Device,
-        contents: &[u8],
-    ) -> (wgpu::Buffer, wgpu::BindGroupLayout, wgpu::BindGroup) {
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Uniform buffer"),
-            contents,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Uniform bind group layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Uniform bind group"),
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-        });
-        (buffer, bind_group_layout, bind_group)
+        canvas: &web_sys::HtmlCanvasElement,
+    ) -> Result<wgpu::Surface<'static>, wgpu::CreateSurfaceError> {
+        instance.create_surface(wgpu::SurfaceTarget::Canvas(canvas.clone()))
     }
 
     fn create_shader_module(device: &wgpu::Device) -> wgpu::ShaderModule {
@@ -327,62 +136,63 @@ impl State {
         })
     }
 
-    fn create_render_pipeline(
-        device: &wgpu::Device,
-        bind_group_layouts: &[&wgpu::BindGroupLayout],
-        shader_module: &wgpu::ShaderModule,
-        surface_config: &wgpu::SurfaceConfiguration,
-    ) -> wgpu::RenderPipeline {
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render pipeline layout"),
-                bind_group_layouts,
-                push_constant_ranges: &[],
-            });
-        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: shader_module,
-                entry_point: "v_main",
-                buffers: &[Vertex::layout()],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: shader_module,
-                entry_point: "f_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-        })
+    /// Logs device loss and uncaptured errors. The animation loop recovers lost devices.
+    fn watch_device(context: &Context) {
+        context.watch(|event| match event {
+            DeviceEvent::Lost { reason, message } => {
+                log!("Device lost ({:?}): {}", reason, message);
+            }
+            DeviceEvent::Error(error) => {
+                log!("Uncaptured error: {}", error);
+            }
+        });
+    }
+
+    /// Recreates the adapter and the device, then moves the renderer onto them.
+    ///
+    /// WebGPU calls this as soon as the device is lost. WebGL2 calls this once the canvas gets
+    /// `webglcontextrestored`, since the context can't be made before that. Either way, the
+    /// animation loop calls this again on the next frame if it fails.
+    async fn recover(&mut self) -> Result<(), Error> {
+        let Target::Surface(surface) = &self.renderer.target else {
+            unreachable!("draws onto the canvas");
+        };
+        let context = self
+            .renderer
+            .context
+            .recreate(&self.instance, Some(&surface.surface))
+            .await?;
+        State::watch_device(&context);
+        let shader_module = State::create_shader_module(&context.device);
+        self.renderer.recover(context, &shader_module)?;
+        self.restored = false;
+        log!("Recovered from device loss");
+        Ok(())
     }
 
     fn create_animation_loop() -> Closure<dyn FnMut(f32)> {
-        Closure::<dyn FnMut(f32)>::new(|time: f32| unsafe {
-            let state = STATE.as_mut().unwrap_unchecked();
-            state.render(time);
-            state.request_animation_frame();
+        Closure::<dyn FnMut(f32)>::new(|time: f32| {
+            with_state(|state| {
+                if state.renderer.context.is_lost() {
+                    // Pauses the loop until a new device is ready.
+                    // WebGL2 waits for the canvas to restore the context first.
+                    if state.backend != wgpu::Backend::Gl || state.restored {
+                        wasm_bindgen_futures::spawn_local(recover());
+                    }
+                    return;
+                }
+                state.render(time);
+                state.request_animation_frame();
+            })
         })
     }
 
+    fn render(&mut self, time: f32) {
+        self.renderer.apply_input(&self.input);
+        self.renderer.render(time);
+        self.input.end_frame();
+    }
+
     #[inline(always)]
     fn request_animation_frame(&self) {
         self.window
@@ -397,72 +207,38 @@ impl State {
             // Synchronize manually
             self.canvas.set_width(new_width);
             self.canvas.set_height(new_height);
-            self.winit_window
-                .set_inner_size(PhysicalSize::new(new_width, new_height));
-            self.surface_config.width = new_width;
-            self.surface_config.height = new_height;
-            self.surface.configure(&self.device, &self.surface_config);
-
-            // Update uniform data
-            self.uniform_data.resolution = [new_width as f32, new_height as f32];
+            self.renderer.resize(new_width, new_height);
 
             log!("Resized: ({}, {})", new_width, new_height);
         }
     }
 
+    // Canvas size isn't scaled, so offsets are already in canvas pixels.
     fn mousemove(&mut self, event: web_sys::MouseEvent) {
-        // Update uniform data
-        self.uniform_data.mouse_move = [event.offset_x() as f32, event.offset_y() as f32];
+        self.input
+            .pointer_move(event.offset_x() as f32, event.offset_y() as f32);
     }
 
     fn click(&mut self, event: web_sys::MouseEvent) {
-        // Update uniform data
-        self.uniform_data.mouse_click = [event.offset_x() as f32, event.offset_y() as f32];
+        self.input
+            .click(event.offset_x() as f32, event.offset_y() as f32);
     }
 
-    fn render(&mut self, time: f32) {
-        // Write uniform data to its buffer
-        self.uniform_data.time = time * 0.001;
-        self.queue.write_buffer(
-            &self.uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[self.uniform_data][..]),
-        );
-
-        let surface_texture = self.surface.get_current_texture().unwrap();
-        let texture_view = surface_texture.texture.create_view(&Default::default());
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render command encoder"),
-            });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &texture_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.draw_indexed(0..self.index_num, 0, 0..1);
-        }
-        self.queue.submit(std::iter::once(encoder.finish()));
-        surface_texture.present();
+    fn mousebuttons(&mut self, event: web_sys::MouseEvent) {
+        self.input.buttons = event.buttons();
+    }
+
+    fn wheel(&mut self, event: web_sys::WheelEvent) {
+        self.input
+            .scroll(event.delta_x() as f32, event.delta_y() as f32);
+    }
+
+    fn keydown(&mut self, event: web_sys::KeyboardEvent) {
+        self.input.key_down(&event.code());
+    }
+
+    fn keyup(&mut self, event: web_sys::KeyboardEvent) {
+        self.input.key_up(&event.code());
     }
 }
 
@@ -472,24 +248,90 @@ pub fn main() {
     console_log::init_with_level(log::Level::Warn).expect("Couldn't initialize logger");
 }
 
+/// Starts drawing, and returns the backend chosen, "WebGPU" or "WebGL2".
+/// Rejects if neither of them is available.
 #[wasm_bindgen]
-pub async fn run() {
-    unsafe {
-        STATE = Some(State::new().await);
-        let state = STATE.as_mut().unwrap_unchecked();
-
-        // Sets resize event listener on window.
-        add_event_listener("", "resize", || STATE.as_mut().unwrap_unchecked().resize());
-
-        // Sets mousemove/click event listener on canvas
-        add_event_listener_with_mouseevent("canvas0", "mousemove", |event: web_sys::MouseEvent| {
-            STATE.as_mut().unwrap_unchecked().mousemove(event)
+pub async fn run() -> Result<String, JsError> {
+    let state = State::new()
+        .await
+        .map_err(|e| JsError::new(&e.to_string()))?;
+
+    // Sets resize event listener on window.
+    add_event_listener("", "resize", || with_state(|state| state.resize()));
+
+    // Sets mouse and wheel event listeners on canvas
+    add_event_listener_with_event("canvas0", "mousemove", |event: web_sys::MouseEvent| {
+        with_state(|state| state.mousemove(event))
+    });
+    add_event_listener_with_event("canvas0", "click", |event: web_sys::MouseEvent| {
+        with_state(|state| state.click(event))
+    });
+    add_event_listener_with_event("canvas0", "mousedown", |event: web_sys::MouseEvent| {
+        with_state(|state| state.mousebuttons(event))
+    });
+    add_event_listener_with_event("canvas0", "mouseup", |event: web_sys::MouseEvent| {
+        with_state(|state| state.mousebuttons(event))
+    });
+    add_event_listener_with_event("canvas0", "wheel", |event: web_sys::WheelEvent| {
+        with_state(|state| state.wheel(event))
+    });
+
+    // Sets keyboard event listeners on window, canvas doesn't get focus by default.
+    add_event_listener_with_event("", "keydown", |event: web_sys::KeyboardEvent| {
+        with_state(|state| state.keydown(event))
+    });
+    add_event_listener_with_event("", "keyup", |event: web_sys::KeyboardEvent| {
+        with_state(|state| state.keyup(event))
+    });
+
+    // wgpu doesn't report lost WebGL2 contexts, so the canvas' events tell.
+    if state.backend == wgpu::Backend::Gl {
+        add_event_listener_with_event("canvas0", "webglcontextlost", |event: web_sys::Event| {
+            // Lets the browser restore the context.
+            event.prevent_default();
+            log!("WebGL2 context lost");
+            with_state(|state| state.renderer.context.set_lost());
         });
-        add_event_listener_with_mouseevent("canvas0", "click", |event: web_sys::MouseEvent| {
-            STATE.as_mut().unwrap_unchecked().click(event)
+        add_event_listener("canvas0", "webglcontextrestored", || {
+            with_state(|state| state.restored = true);
+            wasm_bindgen_futures::spawn_local(recover());
         });
-        state.request_animation_frame();
+    }
+
+    state.request_animation_frame();
+
+    let backend = if state.backend == wgpu::Backend::Gl {
+        "WebGL2"
+    } else {
+        "WebGPU"
+    };
+    STATE.set(Some(state));
+    Ok(backend.to_owned())
+}
+
+/// Recovers from device loss, then resumes the animation loop.
+/// The loop tries again on the next frame if no device is available yet.
+async fn recover() {
+    // Already being recovered if it's been taken out.
+    let Some(mut state) = STATE.take() else {
+        return;
     };
+    if let Err(e) = state.recover().await {
+        log!("Failed to recover: {}", e);
+    }
+    // Events are dropped while the state is taken out. Catches up with resizing.
+    state.resize();
+    state.request_animation_frame();
+    STATE.set(Some(state));
+}
+
+/// Calls the function with the state, unless it's taken out for recovery.
+fn with_state(f: impl FnOnce(&mut State)) {
+    STATE.with_borrow_mut(|state| {
+        if let Some(state) = state {
+            f(state);
+        }
+    });
 }
 
 fn get_window() -> web_sys::Window {
@@ -522,12 +364,13 @@ fn add_event_listener(id: &str, type_: &str, f: impl Fn() + 'static) {
     listener.forget(); // Leak, but it occurs just once
 }
 
-fn add_event_listener_with_mouseevent(
+fn add_event_listener_with_event<E: JsCast + 'static>(
     id: &str,
     type_: &str,
-    f: impl Fn(web_sys::MouseEvent) + 'static,
+    f: impl Fn(E) + 'static,
 ) {
-    let listener = Closure::<dyn Fn(_)>::new(f);
+    let listener =
+        Closure::<dyn Fn(_)>::new(move |event: web_sys::Event| f(event.unchecked_into()));
     if id.is_empty() {
         get_window()
             .add_event_listener_with_callback(type_, listener.as_ref().unchecked_ref())
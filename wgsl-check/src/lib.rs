@@ -0,0 +1,558 @@
+//! Composes and validates WGSL shaders ahead of time, so that broken ones fail `cargo build`
+//! instead of showing up in the browser console.
+//!
+//! [`Check`] finds every `.wgsl` file in a directory. Files having `#define_import_path` are
+//! composable modules, and the others are shaders composed with naga_oil importing them. Each
+//! shader is composed with every combination of the defines it and its imports use, then
+//! validated with naga for each [`Target`]. Errors point at the file and the line when naga can
+//! tell them.
+//!
+//! Defines of `#ifdef` and `#ifndef` are composed undefined and defined. Defines of
+//! `#if NAME op value` are composed with the values compared with and their neighbors, so that
+//! every branch is taken, e.g. 1, 2 and 3 for `#if LEVEL == 2`.
+//!
+//! Examples call [`Check::build`] from their `build.rs`.
+//!
+//! ```ignore
+//! fn main() {
+//!     wgsl_check::Check::new("src")
+//!         .target(wgsl_check::Target::WebGpu)
+//!         .build();
+//! }
+//! ```
+//!
+//! `cargo run -- --target webgl2 ../basic/src` does the same from the command line.
+
+use naga::{back::glsl, valid};
+use naga_oil::compose::{
+    ComposableModuleDescriptor, Composer, NagaModuleDescriptor, ShaderDefValue,
+    get_preprocessor_data,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
+
+/// Where shaders run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target {
+    /// WebGPU in the browser.
+    WebGpu,
+    /// WebGL2 through wgpu's GL backend, which translates shaders into GLSL ES 3.00.
+    WebGl2,
+}
+
+impl Target {
+    /// Shader capabilities of the target.
+    /// WebGL2 has neither cube array textures nor multisampled shading.
+    pub fn capabilities(self) -> valid::Capabilities {
+        match self {
+            Self::WebGpu => valid::Capabilities::default(),
+            Self::WebGl2 => valid::Capabilities::empty(),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "webgpu" => Ok(Self::WebGpu),
+            "webgl2" => Ok(Self::WebGl2),
+            _ => Err(format!("unknown target: {s}, expected webgpu or webgl2")),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WebGpu => f.write_str("WebGPU"),
+            Self::WebGl2 => f.write_str("WebGL2"),
+        }
+    }
+}
+
+/// An invalid shader.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub target: Target,
+    /// Defines the shader was composed with. Empty for modules.
+    pub defs: Vec<String>,
+    /// naga's or naga_oil's message, which shows the file and the line if they're known.
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is invalid for {}", self.path.display(), self.target)?;
+        if !self.defs.is_empty() {
+            write!(f, " with {}", self.defs.join(", "))?;
+        }
+        write!(f, "\n{}", self.message.trim_end())
+    }
+}
+
+/// Error of [`Check::run`].
+#[derive(Debug)]
+pub enum Error {
+    /// The directory or a file in it couldn't be read.
+    Io(PathBuf, io::Error),
+    /// Some shaders are invalid.
+    Invalid(Vec<Diagnostic>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "failed to read {}: {e}", path.display()),
+            Self::Invalid(diagnostics) => {
+                for diagnostic in diagnostics {
+                    writeln!(f, "error: {diagnostic}\n")?;
+                }
+                write!(f, "{} invalid shader(s)", diagnostics.len())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(_, e) => Some(e),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+/// Composes and validates every WGSL file in a directory and its subdirectories.
+#[derive(Debug, Clone)]
+pub struct Check {
+    dir: PathBuf,
+    targets: Vec<Target>,
+}
+
+impl Check {
+    /// Checks files in the directory for [`Target::WebGpu`] unless targets are added.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            targets: Vec::new(),
+        }
+    }
+
+    /// Adds a target the shaders must be valid for.
+    pub fn target(mut self, target: Target) -> Self {
+        if !self.targets.contains(&target) {
+            self.targets.push(target);
+        }
+        self
+    }
+
+    /// For build scripts. Runs the check, and fails the build printing diagnostics if any shader
+    /// is invalid. Cargo runs it again when a file in the directory changes.
+    pub fn build(&self) {
+        println!("cargo::rerun-if-changed={}", self.dir.display());
+        if let Err(e) = self.run() {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+    }
+
+    /// Composes and validates all shaders for all targets.
+    /// Returns how many shader variants have been validated.
+    ///
+    /// Modules are validated as part of shaders importing them, so a module nobody imports isn't.
+    pub fn run(&self) -> Result<usize, Error> {
+        let mut modules = Vec::new();
+        let mut shaders = Vec::new();
+        for path in wgsl_files(&self.dir)? {
+            let file = WgslFile::read(path)?;
+            if file.name.is_some() {
+                modules.push(file);
+            } else {
+                shaders.push(file);
+            }
+        }
+        let modules = sort_by_imports(modules);
+
+        let targets = if self.targets.is_empty() {
+            &[Target::WebGpu][..]
+        } else {
+            &self.targets
+        };
+        let mut checked = 0;
+        let mut diagnostics = Vec::new();
+        for &target in targets {
+            let diagnostic =
+                |file: &WgslFile, defs: &[(&str, ShaderDefValue)], message| Diagnostic {
+                    path: file.path.clone(),
+                    target,
+                    defs: defs
+                        .iter()
+                        .map(|&(name, value)| def_to_string(name, value))
+                        .collect(),
+                    message,
+                };
+
+            let mut composer = Composer::default().with_capabilities(target.capabilities());
+            for module in &modules {
+                if let Err(message) = module.add_to(&mut composer) {
+                    diagnostics.push(diagnostic(module, &[], message));
+                }
+            }
+            for shader in &shaders {
+                let defines = shader.defines_with_imports(&modules);
+                let combinations = match combinations(&defines) {
+                    Ok(combinations) => combinations,
+                    Err(message) => {
+                        diagnostics.push(diagnostic(shader, &[], message));
+                        continue;
+                    }
+                };
+                for defs in combinations {
+                    checked += 1;
+                    if let Err(message) = shader.check(&mut composer, target, &defs) {
+                        diagnostics.push(diagnostic(shader, &defs, message));
+                    }
+                }
+            }
+        }
+
+        if diagnostics.is_empty() {
+            Ok(checked)
+        } else {
+            Err(Error::Invalid(diagnostics))
+        }
+    }
+}
+
+struct WgslFile {
+    path: PathBuf,
+    source: String,
+    /// `#define_import_path` of a module. `None` for shaders.
+    name: Option<String>,
+    /// What `#import`s refer to.
+    imports: Vec<String>,
+    /// Defines used by `#ifdef`, `#ifndef` and `#if`.
+    defines: BTreeMap<String, DefineUse>,
+}
+
+impl WgslFile {
+    fn read(path: PathBuf) -> Result<Self, Error> {
+        let source = fs::read_to_string(&path).map_err(|e| Error::Io(path.clone(), e))?;
+        let (name, imports, _) = get_preprocessor_data(&source);
+        let imports = imports.into_iter().map(|import| import.import).collect();
+        let defines = define_uses(&source);
+        Ok(Self {
+            path,
+            source,
+            name,
+            imports,
+            defines,
+        })
+    }
+
+    fn file_path(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    /// Returns true if the `#import` refers to this module, e.g. `uniform` or `uniform::Data`.
+    fn is_imported_by(&self, import: &str) -> bool {
+        self.name.as_deref().is_some_and(|name| {
+            import == name
+                || import
+                    .strip_prefix(name)
+                    .is_some_and(|rest| rest.starts_with("::"))
+        })
+    }
+
+    fn add_to(&self, composer: &mut Composer) -> Result<(), String> {
+        composer
+            .add_composable_module(ComposableModuleDescriptor {
+                source: &self.source,
+                file_path: &self.file_path(),
+                ..Default::default()
+            })
+            .map(|_| ())
+            .map_err(|e| e.emit_to_string(composer))
+    }
+
+    /// Returns defines of this file and the modules it imports, directly or not.
+    fn defines_with_imports(&self, modules: &[WgslFile]) -> BTreeMap<String, DefineUse> {
+        let mut defines = self.defines.clone();
+        let mut visited = vec![false; modules.len()];
+        let mut imports = self.imports.clone();
+        while let Some(import) = imports.pop() {
+            for (index, module) in modules.iter().enumerate() {
+                if !visited[index] && module.is_imported_by(&import) {
+                    visited[index] = true;
+                    for (name, define) in &module.defines {
+                        defines.entry(name.clone()).or_default().merge(define);
+                    }
+                    imports.extend(module.imports.iter().cloned());
+                }
+            }
+        }
+        defines
+    }
+
+    /// Composes the shader with the defines, then translates it into GLSL for WebGL2.
+    fn check(
+        &self,
+        composer: &mut Composer,
+        target: Target,
+        defs: &[(&str, ShaderDefValue)],
+    ) -> Result<(), String> {
+        let module = composer
+            .make_naga_module(NagaModuleDescriptor {
+                source: &self.source,
+                file_path: &self.file_path(),
+                shader_defs: defs
+                    .iter()
+                    .map(|&(name, value)| (name.to_owned(), value))
+                    .collect(),
+                ..Default::default()
+            })
+            .map_err(|e| e.emit_to_string(composer))?;
+
+        match target {
+            Target::WebGpu => Ok(()),
+            Target::WebGl2 => translate_to_glsl(&module, target.capabilities()),
+        }
+    }
+}
+
+/// Translates each entry point the way wgpu's GL backend does, which rejects what GLSL ES 3.00
+/// can't express, such as storage buffers and compute shaders.
+fn translate_to_glsl(
+    module: &naga::Module,
+    capabilities: valid::Capabilities,
+) -> Result<(), String> {
+    let info = valid::Validator::new(valid::ValidationFlags::all(), capabilities)
+        .validate(module)
+        .map_err(|e| e.into_inner().to_string())?;
+    let options = glsl::Options {
+        version: glsl::Version::Embedded {
+            version: 300,
+            is_webgl: true,
+        },
+        ..Default::default()
+    };
+    for entry_point in &module.entry_points {
+        let pipeline_options = glsl::PipelineOptions {
+            shader_stage: entry_point.stage,
+            entry_point: entry_point.name.clone(),
+            multiview: None,
+        };
+        let mut output = String::new();
+        glsl::Writer::new(
+            &mut output,
+            module,
+            &info,
+            &options,
+            &pipeline_options,
+            naga::proc::BoundsCheckPolicies::default(),
+        )
+        .and_then(|mut writer| writer.write())
+        .map_err(|e| {
+            let name = &entry_point.name;
+            format!("entry point `{name}` can't be translated into GLSL ES 3.00: {e}")
+        })?;
+    }
+    Ok(())
+}
+
+/// Returns `.wgsl` files in the directory and its subdirectories, sorted by path.
+fn wgsl_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir).map_err(|e| Error::Io(dir.clone(), e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| Error::Io(dir.clone(), e))?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "wgsl") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Sorts modules so that each one comes after the modules it imports, as naga_oil requires.
+fn sort_by_imports(modules: Vec<WgslFile>) -> Vec<WgslFile> {
+    fn visit(index: usize, modules: &[WgslFile], visited: &mut [bool], order: &mut Vec<usize>) {
+        if visited[index] {
+            return;
+        }
+        visited[index] = true;
+        for import in &modules[index].imports {
+            if let Some(imported) = modules.iter().position(|m| m.is_imported_by(import)) {
+                visit(imported, modules, visited, order);
+            }
+        }
+        order.push(index);
+    }
+
+    let mut visited = vec![false; modules.len()];
+    let mut order = Vec::with_capacity(modules.len());
+    for index in 0..modules.len() {
+        visit(index, &modules, &mut visited, &mut order);
+    }
+
+    let mut modules = modules.into_iter().map(Some).collect::<Vec<_>>();
+    order
+        .into_iter()
+        .map(|index| modules[index].take().unwrap())
+        .collect()
+}
+
+/// How a file uses a define.
+#[derive(Debug, Clone, Default)]
+struct DefineUse {
+    /// Used by `#ifdef` or `#ifndef`, so it's composed undefined as well.
+    ifdef: bool,
+    /// Values `#if` compares it with, as written.
+    compared: BTreeSet<String>,
+}
+
+impl DefineUse {
+    fn merge(&mut self, other: &DefineUse) {
+        self.ifdef |= other.ifdef;
+        self.compared.extend(other.compared.iter().cloned());
+    }
+
+    /// Returns values to compose with, where `None` means undefined.
+    ///
+    /// Compared values come with their neighbors, which take the other branches of `<`, `>` and
+    /// such. Values naga_oil can't compare with, such as `1.5`, give no values, so the shader
+    /// isn't composed with a value that'd hide the error.
+    fn values(&self) -> Vec<Option<ShaderDefValue>> {
+        let mut values = Vec::new();
+        if self.ifdef {
+            values.push(None);
+        }
+        let compared = self.compared.iter().map(String::as_str);
+        if self.compared.is_empty() {
+            values.push(Some(ShaderDefValue::Bool(true)));
+        } else if compared.clone().all(|v| v.parse::<bool>().is_ok()) {
+            values.extend([false, true].map(|v| Some(ShaderDefValue::Bool(v))));
+        } else if let Ok(ints) = compared
+            .clone()
+            .map(str::parse::<i32>)
+            .collect::<Result<_, _>>()
+        {
+            let ints: BTreeSet<i32> = neighbors(ints, i32::saturating_sub, i32::saturating_add);
+            values.extend(ints.into_iter().map(|v| Some(ShaderDefValue::Int(v))));
+        } else if let Ok(uints) = compared.map(str::parse::<u32>).collect::<Result<_, _>>() {
+            let uints: BTreeSet<u32> = neighbors(uints, u32::saturating_sub, u32::saturating_add);
+            values.extend(uints.into_iter().map(|v| Some(ShaderDefValue::UInt(v))));
+        }
+        values
+    }
+}
+
+/// Returns the values with the ones right before and after them.
+fn neighbors<T: Ord + Copy + From<u8>>(
+    values: Vec<T>,
+    sub: fn(T, T) -> T,
+    add: fn(T, T) -> T,
+) -> BTreeSet<T> {
+    let one = T::from(1);
+    values
+        .into_iter()
+        .flat_map(|v| [sub(v, one), v, add(v, one)])
+        .collect()
+}
+
+/// Returns defines used by `#ifdef`, `#ifndef` and `#if NAME op value`, including their `#else`
+/// forms.
+fn define_uses(source: &str) -> BTreeMap<String, DefineUse> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut defines = BTreeMap::<String, DefineUse>::new();
+    for line in source.lines() {
+        let Some(directive) = line.trim_start().strip_prefix('#') else {
+            continue;
+        };
+        let directive = directive.trim_start();
+        let directive = match directive.strip_prefix("else") {
+            Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim_start(),
+            _ => directive,
+        };
+        let Some((keyword, rest)) = directive.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let (name, rest) = rest.split_at(rest.find(|c| !is_word(c)).unwrap_or(rest.len()));
+        if name.is_empty() {
+            continue;
+        }
+        match keyword {
+            "ifdef" | "ifndef" => defines.entry(name.to_owned()).or_default().ifdef = true,
+            "if" => {
+                // e.g. `#if LEVEL == 2` or `#if LEVEL>=2`
+                let rest = rest.trim_start().trim_start_matches(['=', '!', '<', '>']);
+                let rest = rest.trim_start();
+                let end = rest.find(|c| !is_word(c) && c != '-').unwrap_or(rest.len());
+                let define = defines.entry(name.to_owned()).or_default();
+                define.compared.insert(rest[..end].to_owned());
+            }
+            _ => {}
+        }
+    }
+    defines
+}
+
+/// Most shader variants [`Check::run`] composes for a shader and a target.
+const MAX_VARIANTS: usize = 4096;
+
+/// Returns every combination of the values of the defines. Undefined ones are left out.
+/// Fails if there are more than [`MAX_VARIANTS`] of them, since each define multiplies them.
+fn combinations(
+    defines: &BTreeMap<String, DefineUse>,
+) -> Result<Vec<Vec<(&str, ShaderDefValue)>>, String> {
+    let count = defines.values().try_fold(1_usize, |count, define| {
+        count.checked_mul(define.values().len())
+    });
+    if count.is_none_or(|count| count > MAX_VARIANTS) {
+        let names = defines.keys().map(String::as_str).collect::<Vec<_>>();
+        return Err(format!(
+            "defines {} make more than {MAX_VARIANTS} variants to check",
+            names.join(", ")
+        ));
+    }
+
+    let mut combinations = vec![Vec::new()];
+    for (name, define) in defines {
+        let values = define.values();
+        combinations = combinations
+            .into_iter()
+            .flat_map(|defs: Vec<(&str, ShaderDefValue)>| {
+                values.iter().map(move |value| {
+                    let mut defs = defs.clone();
+                    defs.extend(value.map(|value| (name.as_str(), value)));
+                    defs
+                })
+            })
+            .collect();
+    }
+    Ok(combinations)
+}
+
+/// Formats a define as `NAME` when it's true, or `NAME=value` otherwise.
+fn def_to_string(name: &str, value: ShaderDefValue) -> String {
+    match value {
+        ShaderDefValue::Bool(true) => name.to_owned(),
+        ShaderDefValue::Bool(false) => format!("{name}=false"),
+        ShaderDefValue::Int(v) => format!("{name}={v}"),
+        ShaderDefValue::UInt(v) => format!("{name}={v}"),
+    }
+}
@@ -0,0 +1,58 @@
+//! Checks WGSL files in the given directories, e.g.
+//! `cargo run -- --target webgpu --target webgl2 ../basic/src ../composite-shader/src`
+//!
+//! Both WebGPU and WebGL2 are checked if no `--target` is given.
+
+use std::process;
+use wgsl_check::{Check, Target};
+
+const USAGE: &str = "usage: wgsl-check [--target webgpu|webgl2]... <dir>...";
+
+/// Prints the message and the usage, then exits.
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{message}\n{USAGE}");
+    process::exit(2);
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut targets = Vec::new();
+    let mut dirs = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--target" => {
+                let value = args
+                    .next()
+                    .unwrap_or_else(|| exit_with_usage("--target requires a value"));
+                let target = value
+                    .parse::<Target>()
+                    .unwrap_or_else(|e| exit_with_usage(&e.to_string()));
+                targets.push(target);
+            }
+            _ => dirs.push(arg),
+        }
+    }
+    if dirs.is_empty() {
+        exit_with_usage("no directory is given");
+    }
+    if targets.is_empty() {
+        targets = vec![Target::WebGpu, Target::WebGl2];
+    }
+
+    let mut failed = false;
+    for dir in dirs {
+        let check = targets
+            .iter()
+            .fold(Check::new(&dir), |check, &target| check.target(target));
+        match check.run() {
+            Ok(checked) => println!("{dir}: {checked} shader variant(s) are valid"),
+            Err(e) => {
+                eprintln!("{dir}: {e}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,162 @@
+//! Checks the examples' shaders, and that broken ones are reported with their file and line.
+
+use std::{fs, path::PathBuf};
+use wgsl_check::{Check, Error, Target};
+
+/// Writes the files into a new directory under the target directory.
+fn write_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, source) in files {
+        fs::write(dir.join(file), source).unwrap();
+    }
+    dir
+}
+
+fn messages(result: Result<usize, Error>) -> Vec<String> {
+    match result {
+        Err(Error::Invalid(diagnostics)) => diagnostics.iter().map(|d| d.to_string()).collect(),
+        other => panic!("expected invalid shaders, but got {other:?}"),
+    }
+}
+
+/// Same targets as each example's `build.rs`.
+#[test]
+fn examples() {
+    let examples = [
+        ("basic", &[Target::WebGpu][..]),
+        ("composite-shader", &[Target::WebGpu]),
+        ("offscreen-on-worker", &[Target::WebGpu]),
+        ("webgl-multi-canvas", &[Target::WebGl2]),
+        ("webgpu-or-webgl", &[Target::WebGpu, Target::WebGl2]),
+        ("wgpu-gl-surfaces", &[Target::WebGl2]),
+    ];
+    for (example, targets) in examples {
+        let dir = format!("{}/../{example}/src", env!("CARGO_MANIFEST_DIR"));
+        let check = targets
+            .iter()
+            .fold(Check::new(dir), |check, &target| check.target(target));
+        if let Err(e) = check.run() {
+            panic!("{example}: {e}");
+        }
+    }
+}
+
+/// `top.wgsl` is composed with and without `UNIFORM`, importing `uniform.wgsl`.
+#[test]
+fn compose_every_define() {
+    let dir = format!("{}/../composite-shader/src", env!("CARGO_MANIFEST_DIR"));
+    // monolithic.wgsl, and top.wgsl twice.
+    assert_eq!(Check::new(dir).run().unwrap(), 3);
+}
+
+#[test]
+fn syntax_error() {
+    let dir = write_dir(
+        "syntax_error",
+        &[(
+            "broken.wgsl",
+            "@fragment\nfn f_main() -> @location(0) vec4f {\n    return 1.0\n}\n",
+        )],
+    );
+    let messages = messages(Check::new(dir).run());
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("broken.wgsl:4:"), "{}", messages[0]);
+}
+
+/// An error only under a define is reported with the define, in the module's file.
+#[test]
+fn error_in_imported_module() {
+    let module = "
+#define_import_path colors
+
+#ifdef RED
+fn color() -> vec4f { return vec3f(1.0, 0.0, 0.0); }
+#else
+fn color() -> vec4f { return vec4f(0.0, 0.0, 1.0, 1.0); }
+#endif
+";
+    let shader = "
+#import colors
+
+@fragment
+fn f_main() -> @location(0) vec4f { return colors::color(); }
+";
+    let dir = write_dir(
+        "error_in_imported_module",
+        &[("colors.wgsl", module), ("shader.wgsl", shader)],
+    );
+    let messages = messages(Check::new(dir).run());
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("shader.wgsl is invalid for WebGPU with RED"));
+    assert!(messages[0].contains("colors.wgsl:5:"), "{}", messages[0]);
+}
+
+/// `#if LEVEL == 2` takes every branch with `LEVEL` of 1, 2 and 3, and errors tell the value.
+#[test]
+fn compare_int_define() {
+    let shader = "
+@fragment
+fn f_main() -> @location(0) vec4f {
+#if LEVEL == 2
+    return vec3f(1.0);
+#else if LEVEL > 2
+    return vec4f(1.0);
+#else
+    return vec4f(0.0);
+#endif
+}
+";
+    let dir = write_dir("compare_int_define", &[("level.wgsl", shader)]);
+    let messages = messages(Check::new(&dir).run());
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("with LEVEL=2"), "{}", messages[0]);
+
+    let fixed = shader.replace("vec3f(1.0)", "vec4f(0.5)");
+    let dir = write_dir("compare_int_define", &[("level.wgsl", &fixed)]);
+    assert_eq!(Check::new(&dir).run().unwrap(), 3);
+}
+
+/// 13 defines make 8192 variants, which are too many to check.
+#[test]
+fn too_many_defines() {
+    let mut shader = (0..13)
+        .map(|i| format!("#ifdef DEF{i}\n#endif\n"))
+        .collect::<String>();
+    shader.push_str("@fragment\nfn f_main() -> @location(0) vec4f { return vec4f(1.0); }\n");
+    let dir = write_dir("too_many_defines", &[("many.wgsl", &shader)]);
+    let messages = messages(Check::new(dir).run());
+    assert_eq!(messages.len(), 1);
+    assert!(
+        messages[0].contains("many.wgsl is invalid"),
+        "{}",
+        messages[0]
+    );
+    assert!(
+        messages[0].contains("more than 4096 variants"),
+        "{}",
+        messages[0]
+    );
+}
+
+/// Storage buffers are fine on WebGPU, but WebGL2 doesn't have them.
+#[test]
+fn webgl2_limits() {
+    let shader = "
+@group(0) @binding(0) var<storage> colors: array<vec4f>;
+
+@fragment
+fn f_main() -> @location(0) vec4f { return colors[0]; }
+";
+    let dir = write_dir("webgl2_limits", &[("storage.wgsl", shader)]);
+    assert_eq!(Check::new(&dir).target(Target::WebGpu).run().unwrap(), 1);
+
+    let messages = messages(Check::new(&dir).target(Target::WebGl2).run());
+    assert_eq!(messages.len(), 1);
+    assert!(
+        messages[0].contains("entry point `f_main`"),
+        "{}",
+        messages[0]
+    );
+}